@@ -1,16 +1,13 @@
-use iofs::{
-    fs::stream::{FileReadStream, FileWriteStream},
-    prelude::{FileInfo, Find},
-};
+use iofs::{fs::stream::FileWriteStream, prelude::FileInfo};
 
 fn main() {
     let mut file = FileInfo::open("he");
     let mut txt = FileInfo::open_smart("content").unwrap();
     txt.start_writing().unwrap();
-    file.start_reading().unwrap();
-    while let Ok(buf) = file.read_line::<String>() {
-        let log: Vec<_> = buf.trim().split("\t").collect();
-        txt.writeln(format!("\"{}\" => \"{}\", ", log[0], log[1])).unwrap();
-        txt.writeln(format!("\"{}\" => \"{}\", ", log[2], log[3])).unwrap();
+    for log in file.tsv().unwrap().has_headers(false) {
+        let log = log.unwrap();
+        let field = |i| log.get(i).unwrap_or_default();
+        txt.writeln(format!("\"{}\" => \"{}\", ", field(0), field(1))).unwrap();
+        txt.writeln(format!("\"{}\" => \"{}\", ", field(2), field(3))).unwrap();
     }
 }
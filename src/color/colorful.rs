@@ -4,6 +4,7 @@ use  Color::*;
 
 //static UNDERLINE:&str = "\x1b[4m";
 #[derive(Debug, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Color {
     Black,
     Red,
@@ -19,6 +19,130 @@ impl EnVal {
             Err(VarError::NotPresent)
         }
     }
+
+    /// The home directory of `user`, looked up with `getpwnam_r` on Linux so LDAP and
+    /// other NSS users are found, and in `/etc/passwd` on other unix systems
+    pub fn user_home(user: &str) -> io::Result<String> {
+        #[cfg(target_os = "linux")]
+        if let Some(home) = passwd_home(user) {
+            return Ok(home);
+        }
+        if cfg!(unix) {
+            let passwd = std::fs::read_to_string("/etc/passwd").unwrap_or_default();
+            for line in passwd.lines() {
+                let fields: Vec<&str> = line.split(':').collect();
+                if fields.len() > 5 && fields[0] == user {
+                    return Ok(fields[5].to_string());
+                }
+            }
+        } else if let Ok(home) = EnVal::home() {
+            if let Some(k) = home.trim_end_matches('/').rfind('/') {
+                let home = format!("{}/{}", &home[..k], user);
+                if std::fs::metadata(&home).is_ok() {
+                    return Ok(home);
+                }
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("The home directory of user '{}' cannot be found!", user),
+        ))
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn passwd_home(user: &str) -> Option<String> {
+    use std::ffi::{CStr, CString};
+    let name = CString::new(user).ok()?;
+    let mut buf: Vec<libc::c_char> = vec![0; 1024];
+    loop {
+        let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+        let mut result = std::ptr::null_mut();
+        let code = unsafe {
+            libc::getpwnam_r(
+                name.as_ptr(),
+                &mut pwd,
+                buf.as_mut_ptr(),
+                buf.len(),
+                &mut result,
+            )
+        };
+        match code {
+            libc::ERANGE if buf.len() < 1 << 20 => buf.resize(buf.len() * 2, 0),
+            0 if !result.is_null() && !pwd.pw_dir.is_null() => {
+                let home = unsafe { CStr::from_ptr(pwd.pw_dir) };
+                return Some(home.to_string_lossy().into_owned());
+            }
+            _ => return None,
+        }
+    }
+}
+
+/// Expand a leading `~` or `~user`, and `$VAR`, `${VAR}` and `${VAR:-default}`
+/// anywhere in `path`. The default is used when the variable is unset or empty.
+pub fn expand(path: &str) -> io::Result<String> {
+    let (home, rest) = match path.strip_prefix('~') {
+        Some(rest) => {
+            let end = rest.find(['/', '\\']).unwrap_or(rest.len());
+            let home = match &rest[..end] {
+                "" => EnVal::home().map_err(|e| var_err("HOME", e))?,
+                user => EnVal::user_home(user)?,
+            };
+            (home, &rest[end..])
+        }
+        None => (String::new(), path),
+    };
+    let mut buf = home;
+    let mut rest = rest;
+    while let Some(pos) = rest.find('$') {
+        buf.push_str(&rest[..pos]);
+        let tail = &rest[pos + 1..];
+        let (name, default, len) = if let Some(braced) = tail.strip_prefix('{') {
+            let Some(end) = braced.find('}') else {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    format!("Missing '}}' after '${{' in '{}'!", path),
+                ));
+            };
+            let (name, default) = match braced[..end].split_once(":-") {
+                Some((name, default)) => (name, Some(default)),
+                None => (&braced[..end], None),
+            };
+            (name, default, end + 2)
+        } else {
+            let end = tail
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                .unwrap_or(tail.len());
+            (&tail[..end], None, end)
+        };
+        if name.is_empty() {
+            buf.push('$');
+            rest = tail;
+            continue;
+        }
+        match (env::var(name), default) {
+            (Ok(value), Some(default)) if value.is_empty() => buf.push_str(default),
+            (Ok(value), _) => buf.push_str(&value),
+            (Err(VarError::NotPresent), Some(default)) => buf.push_str(default),
+            (Err(e), _) => return Err(var_err(name, e)),
+        }
+        rest = &tail[len..];
+    }
+    buf.push_str(rest);
+    Ok(buf)
+}
+
+fn var_err(name: &str, e: VarError) -> io::Error {
+    match e {
+        VarError::NotPresent => io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("The environment variable '{}' is not set!", name),
+        ),
+        VarError::NotUnicode(_) => io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("The environment variable '{}' is not valid unicode!", name),
+        ),
+    }
 }
 
 pub fn current_dir() -> io::Result<String> {
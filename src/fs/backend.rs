@@ -0,0 +1,171 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, OnceLock};
+use std::time::SystemTime;
+
+pub type Backend = Arc<dyn FsBackend>;
+
+/// The storage behind `FileInfo`, `DirectoryInfo` and `FileDir`.
+///
+/// All paths are the absolute, normalized paths produced by `PathBuilder`.
+pub trait FsBackend: Send + Sync {
+    fn metadata(&self, path: &Path) -> Result<FsMetadata>;
+    /// The full paths of the direct children of a directory
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>>;
+    fn create_dir_all(&self, path: &Path) -> Result<()>;
+    fn remove_file(&self, path: &Path) -> Result<()>;
+    fn remove_dir_all(&self, path: &Path) -> Result<()>;
+    fn rename(&self, from: &Path, to: &Path) -> Result<()>;
+    fn copy(&self, from: &Path, to: &Path) -> Result<u64>;
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>>;
+    /// Open a file for writing, creating it when needed.
+    /// The file is truncated unless `append` is set.
+    fn open_write(&self, path: &Path, append: bool) -> Result<Box<dyn Write + Send>>;
+    /// Make the written contents of a file durable, only the data unless `all` is set.
+    /// Storage that does not outlive the process has nothing to do.
+    fn sync(&self, _path: &Path, _all: bool) -> Result<()> {
+        Ok(())
+    }
+    /// Give `to` the permissions of `from`, storage without permissions has nothing to do
+    fn copy_permissions(&self, _from: &Path, _to: &Path) -> Result<()> {
+        Ok(())
+    }
+    /// Whether the paths are real paths of the operating system,
+    /// which the readers that seek in or watch a file need
+    fn is_os(&self) -> bool {
+        false
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FsKind {
+    File,
+    Directory,
+    Other,
+}
+
+#[derive(Debug, Clone)]
+pub struct FsMetadata {
+    pub kind: FsKind,
+    pub len: u64,
+    pub modified: SystemTime,
+    pub readonly: bool,
+}
+
+impl FsMetadata {
+    pub fn is_file(&self) -> bool {
+        self.kind == FsKind::File
+    }
+    pub fn is_dir(&self) -> bool {
+        self.kind == FsKind::Directory
+    }
+}
+
+impl From<fs::Metadata> for FsMetadata {
+    fn from(value: fs::Metadata) -> Self {
+        let kind = if value.is_file() {
+            FsKind::File
+        } else if value.is_dir() {
+            FsKind::Directory
+        } else {
+            FsKind::Other
+        };
+        FsMetadata {
+            kind,
+            len: value.len(),
+            modified: value.modified().unwrap_or(SystemTime::UNIX_EPOCH),
+            readonly: value.permissions().readonly(),
+        }
+    }
+}
+
+/// The default backend, which goes straight to `std::fs`
+#[derive(Debug, Clone, Copy, Default)]
+pub struct OsBackend;
+
+impl OsBackend {
+    /// The process wide instance used by `FileInfo::open` and friends
+    pub fn shared() -> Backend {
+        static OS: OnceLock<Backend> = OnceLock::new();
+        OS.get_or_init(|| Arc::new(OsBackend)).clone()
+    }
+}
+
+impl FsBackend for OsBackend {
+    fn metadata(&self, path: &Path) -> Result<FsMetadata> {
+        Ok(fs::metadata(path)?.into())
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let mut children = Vec::new();
+        for entry in fs::read_dir(path)? {
+            match entry {
+                Ok(entry) => children.push(entry.path()),
+                _ => continue,
+            }
+        }
+        Ok(children)
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        fs::create_dir_all(path)
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        fs::remove_file(path)
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        fs::remove_dir_all(path)
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::rename(from, to)
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<u64> {
+        fs::copy(from, to)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        Ok(Box::new(File::open(path)?))
+    }
+
+    fn open_write(&self, path: &Path, append: bool) -> Result<Box<dyn Write + Send>> {
+        let f = OpenOptions::new()
+            .create(true)
+            .write(true)
+            .append(append)
+            .truncate(!append)
+            .open(path)?;
+        Ok(Box::new(f))
+    }
+
+    /// The data of a file is synced through any handle to it, Windows needs write access
+    fn sync(&self, path: &Path, all: bool) -> Result<()> {
+        let f = OpenOptions::new()
+            .read(true)
+            .write(cfg!(windows))
+            .open(path)?;
+        match all {
+            true => f.sync_all(),
+            false => f.sync_data(),
+        }
+    }
+
+    fn copy_permissions(&self, from: &Path, to: &Path) -> Result<()> {
+        fs::set_permissions(to, fs::metadata(from)?.permissions())
+    }
+
+    fn is_os(&self) -> bool {
+        true
+    }
+}
+
+pub(crate) fn os_only_err(what: &str) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!("{} only works on the files of the operating system!", what),
+    )
+}
@@ -0,0 +1,206 @@
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+
+/// The compression used by the read and write streams of a `FileInfo`.
+///
+/// `Auto` detects gzip, zstd and xz by the magic bytes of the file when reading
+/// and by the extension (`.gz`, `.zst`, `.xz`) when writing. Formats whose
+/// feature is not enabled are read and written as they are.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Compression {
+    Auto,
+    None,
+    Gzip,
+    Zstd,
+    Xz,
+}
+
+impl Compression {
+    pub fn from_magic(buf: &[u8]) -> Compression {
+        if buf.starts_with(&[0x1f, 0x8b]) {
+            Compression::Gzip
+        } else if buf.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+            Compression::Zstd
+        } else if buf.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+            Compression::Xz
+        } else {
+            Compression::None
+        }
+    }
+
+    pub fn from_extension(extension: &str) -> Compression {
+        match extension.to_ascii_lowercase().as_str() {
+            ".gz" | ".gzip" | ".tgz" => Compression::Gzip,
+            ".zst" | ".zstd" | ".tzst" => Compression::Zstd,
+            ".xz" | ".txz" => Compression::Xz,
+            _ => Compression::None,
+        }
+    }
+
+    /// The file extension of the format, empty for `Auto` and `None`
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Compression::Gzip => ".gz",
+            Compression::Zstd => ".zst",
+            Compression::Xz => ".xz",
+            _ => "",
+        }
+    }
+
+    /// Resolve `Auto` for reading by peeking at the magic bytes of `reader`,
+    /// falling back to the extension when they are unknown
+    pub(crate) fn for_reading<R: Read>(
+        self,
+        reader: &mut BufReader<R>,
+        extension: &str,
+    ) -> Result<Compression> {
+        if self != Compression::Auto {
+            return Ok(self);
+        }
+        let magic = reader.fill_buf()?;
+        let compression = match Compression::from_magic(magic) {
+            Compression::None if !magic.is_empty() => Compression::from_extension(extension),
+            compression => compression,
+        };
+        Ok(compression.enabled_or_none())
+    }
+
+    pub(crate) fn for_writing(self, extension: &str) -> Compression {
+        match self {
+            Compression::Auto => Compression::from_extension(extension).enabled_or_none(),
+            _ => self,
+        }
+    }
+
+    #[allow(clippy::match_like_matches_macro)]
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Compression::Gzip => cfg!(feature = "gzip"),
+            Compression::Zstd => cfg!(feature = "zstd"),
+            Compression::Xz => cfg!(feature = "xz"),
+            _ => true,
+        }
+    }
+
+    fn enabled_or_none(self) -> Compression {
+        if self.is_enabled() {
+            self
+        } else {
+            Compression::None
+        }
+    }
+
+    pub(crate) fn decoder<R: Read + Send + 'static>(self, f: R) -> Result<Box<dyn Read + Send>> {
+        match self {
+            Compression::Auto | Compression::None => Ok(Box::new(f)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(flate2::read::MultiGzDecoder::new(f))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(zstd::Decoder::new(f)?)),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Box::new(xz2::read::XzDecoder::new_multi_decoder(f))),
+            #[allow(unreachable_patterns)]
+            _ => Err(unsupported_err(self)),
+        }
+    }
+
+    /// Each call starts a new gzip member / zstd frame / xz stream, so appending
+    /// to an existing compressed file keeps it readable
+    pub(crate) fn encoder<W: FinishWrite + 'static>(self, f: W) -> Result<Box<dyn FinishWrite>> {
+        match self {
+            Compression::Auto | Compression::None => Ok(Box::new(f)),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Ok(Box::new(flate2::write::GzEncoder::new(
+                f,
+                flate2::Compression::default(),
+            ))),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Ok(Box::new(ZstdEncoder(zstd::Encoder::new(f, 0)?))),
+            #[cfg(feature = "xz")]
+            Compression::Xz => Ok(Box::new(xz2::write::XzEncoder::new(f, 6))),
+            #[allow(unreachable_patterns)]
+            _ => Err(unsupported_err(self)),
+        }
+    }
+}
+
+/// A writer whose last writes, like the trailer of a compressed stream, are done by
+/// `finish` so that their errors are returned instead of being lost on drop
+pub(crate) trait FinishWrite: Write + Send {
+    fn finish(&mut self) -> Result<()>;
+}
+
+impl FinishWrite for File {
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl<W: Write + Send> FinishWrite for BufWriter<W> {
+    fn finish(&mut self) -> Result<()> {
+        self.flush()
+    }
+}
+
+impl FinishWrite for Box<dyn FinishWrite> {
+    fn finish(&mut self) -> Result<()> {
+        (**self).finish()
+    }
+}
+
+#[cfg(feature = "gzip")]
+impl<W: FinishWrite> FinishWrite for flate2::write::GzEncoder<W> {
+    fn finish(&mut self) -> Result<()> {
+        self.try_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+#[cfg(feature = "xz")]
+impl<W: FinishWrite> FinishWrite for xz2::write::XzEncoder<W> {
+    fn finish(&mut self) -> Result<()> {
+        self.try_finish()?;
+        self.get_mut().finish()
+    }
+}
+
+/// Finishes the frame on drop when `finish` was not called
+#[cfg(feature = "zstd")]
+struct ZstdEncoder<W: Write>(zstd::Encoder<'static, W>);
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Write for ZstdEncoder<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0.write(buf)
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.0.flush()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: FinishWrite> FinishWrite for ZstdEncoder<W> {
+    fn finish(&mut self) -> Result<()> {
+        self.0.do_finish()?;
+        self.0.get_mut().finish()
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl<W: Write> Drop for ZstdEncoder<W> {
+    fn drop(&mut self) {
+        let _ = self.0.do_finish();
+    }
+}
+
+#[allow(unused)]
+fn unsupported_err(compression: Compression) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "{:?} support is not enabled, turn on the matching feature of iofs.",
+            compression
+        ),
+    )
+}
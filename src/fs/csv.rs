@@ -0,0 +1,319 @@
+use super::stream::{FileReadStream, FileWriteStream};
+use super::FileInfo;
+use crate::io::{ConResult, ConvertBuffer, ConvertError};
+use std::fmt::Display;
+use std::io::Result;
+use std::sync::Arc;
+
+/// A reader of delimiter separated values with RFC 4180 quoting.
+///
+/// Quoted fields may contain the delimiter, doubled quotes and line breaks,
+/// blank lines between records are skipped.
+pub struct CsvReader<'a, F: FileReadStream> {
+    stream: &'a mut F,
+    delimiter: u8,
+    quote: u8,
+    has_headers: bool,
+    headers: Option<Arc<[String]>>,
+    line: usize,
+}
+
+/// A writer of delimiter separated values that quotes fields when needed
+pub struct CsvWriter<'a, F: FileWriteStream> {
+    stream: &'a mut F,
+    delimiter: u8,
+    quote: u8,
+    terminator: String,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    fields: Vec<String>,
+    headers: Option<Arc<[String]>>,
+    line: usize,
+}
+
+/// A type built from the fields of a record, implemented for tuples of `ConvertBuffer`
+pub trait FromRecord: Sized {
+    fn from_record(record: &Record) -> ConResult<Self>;
+}
+
+impl FileInfo {
+    /// Start reading the file as comma separated values
+    pub fn csv(&mut self) -> Result<CsvReader<'_, FileInfo>> {
+        self.start_reading()?;
+        Ok(CsvReader::new(self))
+    }
+    /// Start reading the file as tab separated values
+    pub fn tsv(&mut self) -> Result<CsvReader<'_, FileInfo>> {
+        Ok(self.csv()?.delimiter(b'\t'))
+    }
+    /// Start appending comma separated values to the file
+    pub fn csv_writer(&mut self) -> Result<CsvWriter<'_, FileInfo>> {
+        self.start_writing()?;
+        Ok(CsvWriter::new(self))
+    }
+    /// Start appending tab separated values to the file
+    pub fn tsv_writer(&mut self) -> Result<CsvWriter<'_, FileInfo>> {
+        Ok(self.csv_writer()?.delimiter(b'\t'))
+    }
+}
+
+impl<'a, F: FileReadStream> CsvReader<'a, F> {
+    /// Read records from a stream that has already started reading
+    pub fn new(stream: &'a mut F) -> CsvReader<'a, F> {
+        CsvReader {
+            stream,
+            delimiter: b',',
+            quote: b'"',
+            has_headers: true,
+            headers: None,
+            line: 0,
+        }
+    }
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    pub fn quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+    /// Whether the first record is a header row, `true` by default
+    pub fn has_headers(mut self, has_headers: bool) -> Self {
+        self.has_headers = has_headers;
+        self
+    }
+    /// The header row, read on first use
+    pub fn headers(&mut self) -> ConResult<&[String]> {
+        if self.has_headers && self.headers.is_none() {
+            let headers = match self.read_fields() {
+                Some(fields) => fields?.0,
+                None => Vec::new(),
+            };
+            self.headers = Some(headers.into());
+        }
+        match &self.headers {
+            Some(headers) => Ok(headers),
+            None => Err(ConvertError::Empty),
+        }
+    }
+    /// The records converted with `FromRecord`
+    pub fn records<T: FromRecord>(self) -> impl Iterator<Item = ConResult<T>> + 'a
+    where
+        F: 'a,
+    {
+        self.map(|record| T::from_record(&record?))
+    }
+
+    fn read_fields(&mut self) -> Option<ConResult<(Vec<String>, usize)>> {
+        let mut fields = Vec::new();
+        let mut field = Vec::new();
+        let mut start = None;
+        let mut quoted = false;
+        let mut at_start = true;
+        let mut open = (0, 0);
+        loop {
+            let mut line = match self.stream.read_until_raw(b'\n') {
+                Ok(line) => line,
+                Err(ConvertError::Empty) => {
+                    return start.map(|_| Err(unterminated_err(open)));
+                }
+                Err(e) => return Some(Err(e)),
+            };
+            self.line += 1;
+            if line.ends_with(b"\n") {
+                line.pop();
+            }
+            // The `\r` of a `\r\n` ending is kept when the line break is inside quotes
+            let (body, cr) = match line.strip_suffix(b"\r") {
+                Some(body) => (body, true),
+                None => (&line[..], false),
+            };
+            match start {
+                Some(_) => field.push(b'\n'),
+                None if body.is_empty() => continue,
+                None => start = Some(self.line),
+            }
+            let mut bytes = body.iter().enumerate().peekable();
+            while let Some((i, &byte)) = bytes.next() {
+                if quoted {
+                    if byte != self.quote
+                        || bytes.next_if(|(_, &next)| next == self.quote).is_some()
+                    {
+                        field.push(byte);
+                    } else {
+                        quoted = false;
+                    }
+                } else if byte == self.quote && at_start {
+                    quoted = true;
+                    at_start = false;
+                    open = (self.line, i + 1);
+                } else if byte == self.delimiter {
+                    fields.push(std::mem::take(&mut field));
+                    at_start = true;
+                } else {
+                    field.push(byte);
+                    at_start = false;
+                }
+            }
+            if quoted && cr {
+                field.push(b'\r');
+            }
+            if !quoted {
+                fields.push(field);
+                let fields: ConResult<Vec<String>> =
+                    fields.into_iter().map(String::from_buf).collect();
+                return Some(fields.map(|fields| (fields, start.unwrap_or(self.line))));
+            }
+        }
+    }
+}
+
+impl<F: FileReadStream> Iterator for CsvReader<'_, F> {
+    type Item = ConResult<Record>;
+
+    fn next(&mut self) -> Option<ConResult<Record>> {
+        if self.has_headers {
+            if let Err(e) = self.headers() {
+                return Some(Err(e));
+            }
+        }
+        let (fields, line) = match self.read_fields()? {
+            Ok(fields) => fields,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(Ok(Record {
+            fields,
+            headers: self.headers.clone(),
+            line,
+        }))
+    }
+}
+
+impl Record {
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+    pub fn fields(&self) -> &[String] {
+        &self.fields
+    }
+    /// The line the record starts on, starting at 1
+    pub fn line(&self) -> usize {
+        self.line
+    }
+    pub fn get(&self, index: usize) -> Option<&str> {
+        self.fields.get(index).map(|field| field.as_str())
+    }
+    /// The field under the header `name`
+    pub fn get_by(&self, name: &str) -> Option<&str> {
+        let headers = self.headers.as_ref()?;
+        self.get(headers.iter().position(|header| header == name)?)
+    }
+    pub fn parse<B: ConvertBuffer>(&self, index: usize) -> ConResult<B> {
+        match self.get(index) {
+            Some(field) => B::from_buf(field.as_bytes().to_vec()),
+            None => Err(ConvertError::Empty),
+        }
+    }
+    pub fn parse_by<B: ConvertBuffer>(&self, name: &str) -> ConResult<B> {
+        match self.get_by(name) {
+            Some(field) => B::from_buf(field.as_bytes().to_vec()),
+            None => Err(ConvertError::Empty),
+        }
+    }
+}
+
+impl FromRecord for Record {
+    fn from_record(record: &Record) -> ConResult<Self> {
+        Ok(record.clone())
+    }
+}
+
+impl FromRecord for Vec<String> {
+    fn from_record(record: &Record) -> ConResult<Self> {
+        Ok(record.fields.clone())
+    }
+}
+
+macro_rules! derive_tuple {
+    ($(($($t:ident: $i:tt), +)), +) => {
+        $(impl<$($t: ConvertBuffer), +> FromRecord for ($($t,)+) {
+            fn from_record(record: &Record) -> ConResult<Self> {
+                Ok(($(record.parse::<$t>($i)?,)+))
+            }
+        })*
+    };
+}
+derive_tuple!(
+    (A: 0),
+    (A: 0, B: 1),
+    (A: 0, B: 1, C: 2),
+    (A: 0, B: 1, C: 2, D: 3),
+    (A: 0, B: 1, C: 2, D: 3, E: 4),
+    (A: 0, B: 1, C: 2, D: 3, E: 4, G: 5),
+    (A: 0, B: 1, C: 2, D: 3, E: 4, G: 5, H: 6),
+    (A: 0, B: 1, C: 2, D: 3, E: 4, G: 5, H: 6, I: 7)
+);
+
+impl<'a, F: FileWriteStream> CsvWriter<'a, F> {
+    /// Write records to a stream that has already started writing
+    pub fn new(stream: &'a mut F) -> CsvWriter<'a, F> {
+        CsvWriter {
+            stream,
+            delimiter: b',',
+            quote: b'"',
+            terminator: "\n".to_string(),
+        }
+    }
+    pub fn delimiter(mut self, delimiter: u8) -> Self {
+        self.delimiter = delimiter;
+        self
+    }
+    pub fn quote(mut self, quote: u8) -> Self {
+        self.quote = quote;
+        self
+    }
+    /// The line terminator, `\n` by default and `\r\n` for strict RFC 4180
+    pub fn terminator(mut self, terminator: &str) -> Self {
+        self.terminator = terminator.to_string();
+        self
+    }
+    /// Write one record, quoting fields that contain the delimiter, the quote or a line break
+    pub fn write_record<I, S>(&mut self, fields: I) -> Result<()>
+    where
+        I: IntoIterator<Item = S>,
+        S: Display,
+    {
+        let delimiter = self.delimiter as char;
+        let quote = self.quote as char;
+        let mut line = String::new();
+        for (i, field) in fields.into_iter().enumerate() {
+            if i > 0 {
+                line.push(delimiter);
+            }
+            let field = field.to_string();
+            if field.contains([delimiter, quote, '\r', '\n']) {
+                let doubled = format!("{}{}", quote, quote);
+                line.push(quote);
+                line.push_str(&field.replace(quote, &doubled));
+                line.push(quote);
+            } else {
+                line.push_str(&field);
+            }
+        }
+        line.push_str(&self.terminator);
+        self.stream.write(line)
+    }
+}
+
+fn unterminated_err((line, column): (usize, usize)) -> ConvertError {
+    ConvertError::Parse {
+        line,
+        column,
+        message: "The quoted field is not terminated!".to_string(),
+    }
+}
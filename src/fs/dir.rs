@@ -1,15 +1,14 @@
+use super::backend::{Backend, FsBackend, OsBackend};
 use super::{fd::DFiles, fd::FileDir, FileInfo};
 use crate::fs::pbuilder::PathBuilder;
 use crate::fs::Attributes;
-use std::fs::{metadata, read_dir};
-use std::{
-    fmt::Debug,
-    fs::{self, DirBuilder},
-    io::Result,
-};
+use std::fs::DirEntry;
+use std::path::{Path, PathBuf};
+use std::{fmt::Debug, io::Result};
 #[derive(Clone)]
 pub struct DirectoryInfo {
     inner: PathBuilder,
+    backend: Backend,
 }
 
 impl PartialEq for DirectoryInfo {
@@ -28,7 +27,7 @@ impl Debug for DirectoryInfo {
 
 impl DFiles for DirectoryInfo {
     fn is_exist(&self) -> bool {
-        read_dir(self.full_name()).is_ok()
+        self.backend.read_dir(self.as_path()).is_ok()
     }
     fn attributes(&self) -> Attributes {
         if self.is_exist() {
@@ -46,17 +45,31 @@ impl DFiles for DirectoryInfo {
         self.inner.mut_borrow()
     }
 
+    fn backend(&self) -> &dyn FsBackend {
+        &*self.backend
+    }
+
+    fn shared_backend(&self) -> Backend {
+        self.backend.clone()
+    }
+
     fn copy_new(&self, path: &str) -> Result<()> {
-        let builder = PathBuilder::from(path);
-        self.move_or_copy(builder.full_name(), false)?;
+        self.copy_path(&PathBuilder::from(path))
+    }
+
+    fn copy_path(&self, path: &PathBuilder) -> Result<()> {
+        self.move_or_copy(path, false)?;
         Ok(())
     }
 
     fn move_new(&mut self, path: &str) -> Result<()> {
-        let builder = PathBuilder::from(path);
-        self.move_or_copy(builder.full_name(), true)?;
-        fs::remove_dir_all(self.full_name())?;
-        *self = unsafe { DirectoryInfo::open_uncheck(builder.full_name()) };
+        self.move_path(&PathBuilder::from(path))
+    }
+
+    fn move_path(&mut self, path: &PathBuilder) -> Result<()> {
+        self.move_or_copy(path, true)?;
+        self.backend.remove_dir_all(self.as_path())?;
+        *self = DirectoryInfo::from_builder(path.clone(), self.backend.clone());
         Ok(())
     }
     fn size_bytes(&self) -> u64 {
@@ -70,26 +83,100 @@ impl DFiles for DirectoryInfo {
         size
     }
 }
+/// Serialized as the full path, deserialized with `OsBackend`
+#[cfg(feature = "serde")]
+impl serde::Serialize for DirectoryInfo {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.builder().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DirectoryInfo {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let path = PathBuilder::deserialize(deserializer)?;
+        Ok(DirectoryInfo::from_builder(path, OsBackend::shared()))
+    }
+}
+
+impl From<&Path> for DirectoryInfo {
+    fn from(value: &Path) -> Self {
+        DirectoryInfo::from_path(value)
+    }
+}
+
+impl From<PathBuf> for DirectoryInfo {
+    fn from(value: PathBuf) -> Self {
+        DirectoryInfo::from_path(value)
+    }
+}
+
+impl From<DirEntry> for DirectoryInfo {
+    fn from(value: DirEntry) -> Self {
+        DirectoryInfo::from_path(value.path())
+    }
+}
+
+impl From<DirectoryInfo> for PathBuf {
+    fn from(value: DirectoryInfo) -> Self {
+        value.inner.into()
+    }
+}
+
+impl AsRef<Path> for DirectoryInfo {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
 impl DirectoryInfo {
     pub fn open<P: AsRef<str>>(path: P) -> DirectoryInfo {
+        DirectoryInfo::open_in(path, OsBackend::shared())
+    }
+    pub fn open_in<P: AsRef<str>>(path: P, backend: Backend) -> DirectoryInfo {
         DirectoryInfo {
             inner: PathBuilder::from(path),
+            backend,
         }
     }
+    /// Open with `~`, `~user` and environment variables in `path` expanded
+    pub fn open_expanded<P: AsRef<str>>(path: P) -> Result<DirectoryInfo> {
+        Ok(DirectoryInfo::from_builder(
+            PathBuilder::expand(path)?,
+            OsBackend::shared(),
+        ))
+    }
+    pub fn from_path<P: AsRef<Path>>(path: P) -> DirectoryInfo {
+        DirectoryInfo::from_builder(PathBuilder::from_path(path), OsBackend::shared())
+    }
 
     pub unsafe fn open_uncheck<P: AsRef<str>>(path: P) -> DirectoryInfo {
         DirectoryInfo {
             inner: PathBuilder::from_uncheck(path),
+            backend: OsBackend::shared(),
         }
     }
+    pub(crate) fn from_builder(inner: PathBuilder, backend: Backend) -> DirectoryInfo {
+        DirectoryInfo { inner, backend }
+    }
     pub fn open_smart<P: AsRef<str>>(path: P) -> Result<DirectoryInfo> {
-        if metadata(path.as_ref()).is_err() {
-            DirBuilder::new().recursive(true).create(path.as_ref())?;
+        DirectoryInfo::open_smart_in(path, OsBackend::shared())
+    }
+    /// Open `path` in `backend`, creating it and its parents when it does not exist
+    pub fn open_smart_in<P: AsRef<str>>(path: P, backend: Backend) -> Result<DirectoryInfo> {
+        let dir = DirectoryInfo::open_in(path, backend);
+        if dir.backend.metadata(dir.as_path()).is_err() {
+            dir.backend.create_dir_all(dir.as_path())?;
         }
-        Ok(DirectoryInfo::open(path))
+        Ok(dir)
     }
     pub fn create(&mut self) -> Result<()> {
-        DirBuilder::new().recursive(true).create(self.full_name())
+        self.backend.create_dir_all(self.as_path())
     }
 
     pub fn is_eq(&self, other: &Self) -> bool {
@@ -125,16 +212,12 @@ impl DirectoryInfo {
         }
         self == other || is_eq(self, other)
     }
-    fn find_children(&self, is_dir: bool, is_file: bool) -> Vec<String> {
-        let child = |dir: &DirectoryInfo| -> Result<Vec<String>> {
+    fn find_children(&self, is_dir: bool, is_file: bool) -> Vec<PathBuf> {
+        let child = |dir: &DirectoryInfo| -> Result<Vec<PathBuf>> {
             let mut child = Vec::new();
-            let paths = read_dir(dir.full_name())?;
+            let paths = dir.backend.read_dir(dir.as_path())?;
             for path in paths {
-                let path = match path {
-                    Ok(path) => path.path().display().to_string().replace("\\", "/"),
-                    _ => continue,
-                };
-                let data = match metadata(&path) {
+                let data = match dir.backend.metadata(&path) {
                     Ok(data) => data,
                     _ => continue,
                 };
@@ -149,45 +232,54 @@ impl DirectoryInfo {
     pub fn files(&self) -> Vec<FileInfo> {
         self.find_children(false, true)
             .into_iter()
-            .map(|path| unsafe { FileInfo::open_uncheck(path) })
+            .map(|path| FileInfo::from_builder(PathBuilder::from_path(path), self.backend.clone()))
             .collect()
     }
+    /// The paths of the files, lossy when they are not valid UTF-8
     pub fn file_paths(&self) -> Vec<String> {
-        self.find_children(false, true)
+        lossy(self.find_children(false, true))
     }
     pub fn directories(&self) -> Vec<DirectoryInfo> {
         self.find_children(true, false)
             .into_iter()
-            .map(|path| unsafe { DirectoryInfo::open_uncheck(path) })
+            .map(|path| {
+                DirectoryInfo::from_builder(PathBuilder::from_path(path), self.backend.clone())
+            })
             .collect()
     }
     pub fn directory_paths(&self) -> Vec<String> {
-        self.find_children(true, false)
+        lossy(self.find_children(true, false))
     }
     pub fn children(&self) -> Vec<FileDir> {
         self.find_children(true, true)
             .into_iter()
-            .map(|path| unsafe { FileDir::open_uncheck(path) })
+            .filter_map(|path| {
+                FileDir::open_path_in(PathBuilder::from_path(path), self.backend.clone()).ok()
+            })
             .collect()
     }
     pub fn children_path(&self) -> Vec<String> {
-        self.find_children(true, true)
+        lossy(self.find_children(true, true))
     }
-    pub fn contain_child(&self, child: impl AsRef<str>) -> bool {
-        metadata(format!("{}/{}", self.full_name(), child.as_ref())).is_ok()
+    pub fn contain_child(&self, child: impl AsRef<Path>) -> bool {
+        self.backend.metadata(&self.as_path().join(child)).is_ok()
     }
-    fn move_or_copy(&self, path: &str, is_move: bool) -> Result<()> {
+    fn move_or_copy(&self, path: &PathBuilder, is_move: bool) -> Result<()> {
         let mut queue = vec![self.clone()];
         while !queue.is_empty() {
             let dir = queue.remove(0);
             queue.append(&mut dir.directories());
-            let full_name = dir.full_name().replace(self.full_name(), path);
-            fs::create_dir_all(&full_name)?;
+            let dest = match dir.as_path().strip_prefix(self.as_path()) {
+                Ok(rel) => path.join(rel),
+                _ => path.clone(),
+            };
+            self.backend.create_dir_all(dest.as_path())?;
             for mut f in dir.files() {
+                let dest = dest.join(f.builder().os_name());
                 if is_move {
-                    f.move_to(&full_name)?;
+                    f.move_path(&dest)?;
                 } else {
-                    f.copy_to(&full_name)?;
+                    f.copy_path(&dest)?;
                 }
             }
         }
@@ -197,3 +289,10 @@ impl DirectoryInfo {
         FileDir::from(self)
     }
 }
+
+fn lossy(paths: Vec<PathBuf>) -> Vec<String> {
+    paths
+        .into_iter()
+        .map(|path| PathBuilder::from_path(path).string_clone())
+        .collect()
+}
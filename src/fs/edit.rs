@@ -0,0 +1,161 @@
+use super::stream::LineEnding;
+use super::{DFiles, FileInfo};
+use std::io::{Error, ErrorKind, Read, Result};
+use std::path::PathBuf;
+
+/// In-place edits that rewrite the file with `write_atomic` and only when something changed.
+/// The old contents are kept first when a backup suffix is set with `set_backup`.
+impl FileInfo {
+    /// Call `f` with the index and text of each line without its line ending,
+    /// returns the number of lines `f` changed
+    pub fn edit_lines<F: FnMut(usize, &mut String)>(&self, mut f: F) -> Result<usize> {
+        let text = self.read_text()?;
+        let mut lines = split_lines(&text);
+        let mut changed = 0;
+        for (i, (line, _)) in lines.iter_mut().enumerate() {
+            let old = line.clone();
+            f(i, line);
+            if *line != old {
+                changed += 1;
+            }
+        }
+        if changed > 0 {
+            self.rewrite(join_lines(&lines))?;
+        }
+        Ok(changed)
+    }
+    /// Replace every `pattern` with `replacement`, returns the number of replacements
+    pub fn replace_all(&self, pattern: &str, replacement: &str) -> Result<usize> {
+        if pattern.is_empty() {
+            return Err(empty_pattern_err());
+        }
+        let text = self.read_text()?;
+        let count = text.matches(pattern).count();
+        if count > 0 {
+            self.rewrite(text.replace(pattern, replacement))?;
+        }
+        Ok(count)
+    }
+    /// Replace every match of the regex `pattern` with `replacement`, which may refer
+    /// to groups as `$1` or `$name`, returns the number of replacements
+    #[cfg(feature = "regex")]
+    pub fn replace_regex(&self, pattern: &str, replacement: &str) -> Result<usize> {
+        let regex = match regex::Regex::new(pattern) {
+            Ok(regex) => regex,
+            Err(e) => return Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        };
+        let text = self.read_text()?;
+        let count = regex.find_iter(&text).count();
+        if count > 0 {
+            self.rewrite(regex.replace_all(&text, replacement).into_owned())?;
+        }
+        Ok(count)
+    }
+    /// Insert `line` before the line `index`, or after the last line when `index` is the number of lines.
+    /// The new line ends like its neighbours.
+    pub fn insert_line(&self, index: usize, line: &str) -> Result<()> {
+        let text = self.read_text()?;
+        let mut lines = split_lines(&text);
+        if index > lines.len() {
+            return Err(out_of_range_err(index, lines.len()));
+        }
+        let eol = LineEnding::detect(text.as_bytes())
+            .unwrap_or(LineEnding::Native)
+            .as_str();
+        let ending = if index < lines.len() {
+            match lines[index].1 {
+                "" if index > 0 => lines[index - 1].1,
+                "" => eol,
+                ending => ending,
+            }
+        } else {
+            // Appending keeps whether the file ends with a line break
+            match lines.last_mut() {
+                Some((_, last)) if last.is_empty() => {
+                    *last = eol;
+                    ""
+                }
+                Some((_, last)) => last,
+                None => eol,
+            }
+        };
+        lines.insert(index, (line.to_string(), ending));
+        self.rewrite(join_lines(&lines))
+    }
+    /// Delete the line `index`, returns the deleted line
+    pub fn delete_line(&self, index: usize) -> Result<String> {
+        let text = self.read_text()?;
+        let mut lines = split_lines(&text);
+        if index >= lines.len() {
+            return Err(out_of_range_err(index, lines.len()));
+        }
+        let (line, removed) = lines.remove(index);
+        // The file did not end in a line ending, neither does the new last line
+        if index == lines.len() && removed.is_empty() {
+            if let Some((_, ending)) = lines.last_mut() {
+                *ending = "";
+            }
+        }
+        self.rewrite(join_lines(&lines))?;
+        Ok(line)
+    }
+
+    fn read_text(&self) -> Result<String> {
+        let mut text = String::new();
+        self.reader()?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    fn rewrite(&self, text: String) -> Result<()> {
+        if let Some(suffix) = self.backup() {
+            let mut backup = self.as_path().as_os_str().to_os_string();
+            backup.push(suffix);
+            self.backend()
+                .copy(self.as_path(), &PathBuf::from(backup))?;
+        }
+        self.write_atomic(text)
+    }
+}
+
+/// The lines of `text` with their line endings, `\n`, `\r\n`, `\r` or empty for the last line
+fn split_lines(text: &str) -> Vec<(String, &'static str)> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        let Some(end) = rest.find(['\n', '\r']) else {
+            lines.push((rest.to_string(), ""));
+            break;
+        };
+        let ending = match &rest[end..] {
+            ending if ending.starts_with("\r\n") => "\r\n",
+            ending if ending.starts_with('\r') => "\r",
+            _ => "\n",
+        };
+        lines.push((rest[..end].to_string(), ending));
+        rest = &rest[end + ending.len()..];
+    }
+    lines
+}
+
+fn join_lines(lines: &[(String, &str)]) -> String {
+    let mut text = String::new();
+    for (line, ending) in lines {
+        text.push_str(line);
+        text.push_str(ending);
+    }
+    text
+}
+
+fn empty_pattern_err() -> Error {
+    Error::new(ErrorKind::InvalidInput, "The pattern is empty!")
+}
+
+fn out_of_range_err(index: usize, len: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidInput,
+        format!(
+            "The line {} is out of range, the file has {} lines!",
+            index, len
+        ),
+    )
+}
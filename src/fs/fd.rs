@@ -1,7 +1,9 @@
+use super::backend::{Backend, FsBackend, FsMetadata, OsBackend};
 use super::{DirectoryInfo, FileInfo};
-use crate::fs::pbuilder::PathBuilder;
-use std::fs::{self, metadata, Metadata};
+use crate::fs::pbuilder::{Ancestors, Components, PathBuilder};
+use std::fs::{metadata, DirEntry, Metadata};
 use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
 use std::time::SystemTime;
 
 pub trait DFiles {
@@ -22,6 +24,9 @@ pub trait DFiles {
     fn full_name(&self) -> &str {
         self.builder().full_name()
     }
+    fn as_path(&self) -> &Path {
+        self.builder().as_path()
+    }
     fn is_exist(&self) -> bool;
     fn attributes(&self) -> Attributes;
 
@@ -29,20 +34,26 @@ pub trait DFiles {
         self.name().starts_with(".")
     }
     fn rename(&mut self, new_name: &str) -> Result<()> {
-        let parent = self.parent_str();
-        let extension = self.extension();
-        fs::rename(
-            format!("{}/{}{}", parent, self.ext_name(), extension),
-            format!("{}/{}{}", parent, new_name, extension),
-        )?;
+        let mut builder = self.builder().clone();
+        builder.rename(new_name);
+        self.backend().rename(self.as_path(), builder.as_path())?;
         unsafe {
-            self.mut_builder().rename(new_name);
+            *self.mut_builder() = builder;
         }
         Ok(())
     }
     fn extension(&self) -> &str {
         self.builder().extension()
     }
+    fn full_extension(&self) -> &str {
+        self.builder().full_extension()
+    }
+    fn extensions(&self) -> Vec<&str> {
+        self.builder().extensions()
+    }
+    fn stem(&self) -> &str {
+        self.builder().stem()
+    }
     fn extension_match(&self, needle: &[&str]) -> bool {
         let extension = self.extension();
         for pat in needle {
@@ -63,59 +74,85 @@ pub trait DFiles {
     }
 
     fn set_extension(&mut self, exs: &str) -> Result<()> {
+        let mut builder = self.builder().clone();
+        builder.set_extension(exs);
+        self.backend().rename(self.as_path(), builder.as_path())?;
         unsafe {
-            let exs = if exs.starts_with(".") {
-                exs.to_owned()
-            } else {
-                format!(".{}", exs)
-            };
-            fs::rename(
-                self.full_name(),
-                format!("{}/{}{}", self.parent_str(), self.ext_name(), exs),
-            )?;
-            self.mut_builder().set_extension(exs.as_str());
+            *self.mut_builder() = builder;
         }
         Ok(())
     }
     fn del(&self) -> Result<()> {
         match self.attributes() {
-            Attributes::File => fs::remove_file(self.full_name()),
-            Attributes::Directory => fs::remove_dir_all(self.full_name()),
+            Attributes::File => self.backend().remove_file(self.as_path()),
+            Attributes::Directory => self.backend().remove_dir_all(self.as_path()),
             _ => Ok(()),
         }
     }
     fn builder(&self) -> &PathBuilder;
     unsafe fn mut_builder(&mut self) -> &mut PathBuilder;
+    /// The backend every operation goes through, `OsBackend` unless overridden
+    fn backend(&self) -> &dyn FsBackend {
+        &OsBackend
+    }
+    /// The backend as a shared handle, kept by the directories `parent` and `common_ancestor` open
+    fn shared_backend(&self) -> Backend {
+        OsBackend::shared()
+    }
     fn copy_to(&self, path: &str) -> Result<()> {
-        self.copy_new(&format!("{}/{}", path, self.name()))
+        self.copy_path(&PathBuilder::from(path).join(self.builder().os_name()))
     }
     fn copy_new(&self, path: &str) -> Result<()>;
+    /// Copy to exactly `path`, which may not be valid UTF-8.
+    /// Goes through `copy_new` unless implemented, which needs a UTF-8 path.
+    fn copy_path(&self, path: &PathBuilder) -> Result<()> {
+        match path.as_path().to_str() {
+            Some(path) => self.copy_new(path),
+            None => Err(not_utf8_err()),
+        }
+    }
     fn move_to(&mut self, path: &str) -> Result<()> {
-        self.move_new(&format!("{}/{}", path, self.name()))
+        self.move_path(&PathBuilder::from(path).join(self.builder().os_name()))
     }
     fn move_new(&mut self, path: &str) -> Result<()>;
+    /// Move to exactly `path`, which may not be valid UTF-8.
+    /// Goes through `move_new` unless implemented, which needs a UTF-8 path.
+    fn move_path(&mut self, path: &PathBuilder) -> Result<()> {
+        match path.as_path().to_str() {
+            Some(path) => self.move_new(path),
+            None => Err(not_utf8_err()),
+        }
+    }
     fn cover_to(&mut self, path: &str, is_move: bool) -> Result<()> {
-        self.cover_new(format!("{}/{}", path, self.name()).as_str(), is_move)
+        let builder = PathBuilder::from(path).join(self.builder().os_name());
+        self.cover_path(&builder, is_move)
     }
     fn cover_new(&mut self, path: &str, is_move: bool) -> Result<()> {
-        let builder = PathBuilder::from(path);
-        if builder.full_name() == self.full_name() {
+        self.cover_path(&PathBuilder::from(path), is_move)
+    }
+    fn cover_path(&mut self, path: &PathBuilder, is_move: bool) -> Result<()> {
+        if path == self.builder() {
             return Ok(());
         }
-        check(builder.full_name())?;
+        check(self.backend(), path.as_path())?;
         if is_move {
-            self.move_new(builder.full_name())
+            self.move_path(path)
         } else {
-            self.copy_new(builder.full_name())
+            self.copy_path(path)
         }
     }
 
+    /// The metadata from the operating system, ignoring the backend
     fn metadata(&self) -> Result<Metadata> {
-        metadata(self.full_name())
+        metadata(self.as_path())
+    }
+    /// The metadata from the backend
+    fn stat(&self) -> Result<FsMetadata> {
+        self.backend().metadata(self.as_path())
     }
     fn modified(&self) -> Result<SystemTime> {
         if self.is_exist() {
-            self.metadata()?.modified()
+            Ok(self.stat()?.modified)
         } else {
             Err(not_found_err())
         }
@@ -128,8 +165,8 @@ pub trait DFiles {
         self.size_kb() / 1024
     }
     fn is_read_only(&self) -> bool {
-        match metadata(self.full_name()) {
-            Ok(data) => data.permissions().readonly(),
+        match self.stat() {
+            Ok(data) => data.readonly,
             _ => false,
         }
     }
@@ -137,17 +174,49 @@ pub trait DFiles {
         self.builder().parent()
     }
     fn parent(&self) -> DirectoryInfo {
-        DirectoryInfo::open(self.parent_str())
+        DirectoryInfo::open_in(self.parent_str(), self.shared_backend())
+    }
+    fn canonicalize(&self) -> Result<PathBuilder> {
+        self.builder().canonicalize()
+    }
+    /// Whether both point to the same place once symlinks are resolved
+    fn same_path(&self, other: &dyn DFiles) -> bool {
+        self.builder().canonical_eq(other.builder())
+    }
+    fn components(&self) -> Components<'_> {
+        self.builder().components()
+    }
+    fn ancestors(&self) -> Ancestors<'_> {
+        self.builder().ancestors()
+    }
+    fn join(&self, path: &str) -> PathBuilder {
+        self.builder().join(path)
+    }
+    fn starts_with(&self, base: &dyn DFiles) -> bool {
+        self.builder().starts_with(base.builder())
+    }
+    fn ends_with(&self, suffix: &str) -> bool {
+        self.builder().ends_with(suffix)
+    }
+    fn strip_prefix(&self, base: &dyn DFiles) -> Option<&str> {
+        self.builder().strip_prefix(base.builder())
+    }
+    fn relative_to(&self, base: &dyn DFiles) -> Option<String> {
+        self.builder().relative_to(base.builder())
+    }
+    fn common_ancestor(&self, other: &dyn DFiles) -> Option<DirectoryInfo> {
+        let path = self.builder().common_ancestor(other.builder())?;
+        Some(DirectoryInfo::from_builder(path, self.shared_backend()))
     }
 }
 
-fn check<P: AsRef<str>>(path: P) -> Result<()> {
-    match metadata(path.as_ref()) {
+fn check(backend: &dyn FsBackend, path: &Path) -> Result<()> {
+    match backend.metadata(path) {
         Ok(data) => {
             if data.is_dir() {
-                fs::remove_dir_all(path.as_ref())
+                backend.remove_dir_all(path)
             } else {
-                fs::remove_file(path.as_ref())
+                backend.remove_file(path)
             }
         }
         _ => Ok(()),
@@ -155,6 +224,7 @@ fn check<P: AsRef<str>>(path: P) -> Result<()> {
 }
 
 /// None != None
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Attributes {
     File,
     Directory,
@@ -178,11 +248,13 @@ impl PartialEq for Attributes {
 }
 pub struct FileDir {
     inner: Box<dyn DFiles>,
+    backend: Backend,
 }
 
 impl From<FileInfo> for FileDir {
     fn from(value: FileInfo) -> Self {
         Self {
+            backend: value.shared_backend(),
             inner: Box::new(value),
         }
     }
@@ -190,14 +262,62 @@ impl From<FileInfo> for FileDir {
 impl From<DirectoryInfo> for FileDir {
     fn from(value: DirectoryInfo) -> Self {
         Self {
+            backend: value.shared_backend(),
             inner: Box::new(value),
         }
     }
 }
 
+impl From<DirEntry> for FileDir {
+    fn from(value: DirEntry) -> Self {
+        let path = value.path();
+        if path.is_dir() {
+            DirectoryInfo::from(path).into()
+        } else {
+            FileInfo::from(path).into()
+        }
+    }
+}
+
+impl From<FileDir> for PathBuf {
+    fn from(value: FileDir) -> Self {
+        value.as_path().to_path_buf()
+    }
+}
+
+impl AsRef<Path> for FileDir {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
 impl From<Box<dyn DFiles>> for FileDir {
     fn from(value: Box<dyn DFiles>) -> Self {
-        FileDir { inner: value }
+        FileDir {
+            backend: value.shared_backend(),
+            inner: value,
+        }
+    }
+}
+
+/// Serialized as the full path, the path has to exist to deserialize
+#[cfg(feature = "serde")]
+impl serde::Serialize for FileDir {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.full_name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FileDir {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let path = String::deserialize(deserializer)?;
+        FileDir::open(&path).map_err(serde::de::Error::custom)
     }
 }
 
@@ -218,14 +338,30 @@ impl DFiles for FileDir {
         self.inner.mut_builder()
     }
 
+    fn backend(&self) -> &dyn FsBackend {
+        self.inner.backend()
+    }
+
+    fn shared_backend(&self) -> Backend {
+        self.backend.clone()
+    }
+
     fn copy_new(&self, path: &str) -> Result<()> {
         self.inner.copy_new(path)
     }
 
+    fn copy_path(&self, path: &PathBuilder) -> Result<()> {
+        self.inner.copy_path(path)
+    }
+
     fn move_new(&mut self, path: &str) -> Result<()> {
         self.inner.move_new(path)
     }
 
+    fn move_path(&mut self, path: &PathBuilder) -> Result<()> {
+        self.inner.move_path(path)
+    }
+
     fn size_bytes(&self) -> u64 {
         self.inner.size_bytes()
     }
@@ -233,28 +369,36 @@ impl DFiles for FileDir {
 
 impl FileDir {
     pub fn open(path: &str) -> Result<FileDir> {
-        let inner = to_file_dir(path)?;
-        Ok(FileDir { inner })
+        FileDir::open_in(path, OsBackend::shared())
+    }
+    pub fn open_in(path: &str, backend: Backend) -> Result<FileDir> {
+        let inner = to_file_dir(path, &backend)?;
+        Ok(FileDir { inner, backend })
+    }
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Result<FileDir> {
+        FileDir::open_path_in(PathBuilder::from_path(path), OsBackend::shared())
     }
     pub unsafe fn open_uncheck<P: AsRef<str>>(path: P) -> FileDir {
-        let inner = match metadata(path.as_ref()) {
-            Ok(data) => {
-                if data.is_dir() {
-                    Box::new(DirectoryInfo::open_uncheck(path))
-                } else {
-                    Box::new(FileInfo::open_uncheck(path)) as Box<dyn DFiles>
-                }
-            }
+        match FileDir::open_path_in(PathBuilder::from_uncheck(path), OsBackend::shared()) {
+            Ok(fd) => fd,
             _ => panic!("Cannot find the specified file or directory!"),
+        }
+    }
+    /// Open an already built path, which may not be valid UTF-8
+    pub(crate) fn open_path_in(path: PathBuilder, backend: Backend) -> Result<FileDir> {
+        let inner = if backend.metadata(path.as_path())?.is_dir() {
+            Box::new(DirectoryInfo::from_builder(path, backend.clone())) as Box<dyn DFiles>
+        } else {
+            Box::new(FileInfo::from_builder(path, backend.clone()))
         };
-        FileDir { inner }
+        Ok(FileDir { inner, backend })
     }
 
     fn to_file(&self) -> FileInfo {
-        unsafe { FileInfo::open_uncheck(self.full_name()) }
+        FileInfo::from_builder(self.builder().clone(), self.backend.clone())
     }
-    fn to_dir(&self) -> DirectoryInfo {
-        unsafe { DirectoryInfo::open_uncheck(self.full_name()) }
+    pub(crate) fn to_dir(&self) -> DirectoryInfo {
+        DirectoryInfo::from_builder(self.builder().clone(), self.backend.clone())
     }
     pub fn is_eq(&self, other: &Self) -> bool {
         match self.common_attr_with(other) {
@@ -265,13 +409,13 @@ impl FileDir {
     }
 
     pub fn is_dir(&self) -> bool {
-        match self.metadata() {
+        match self.stat() {
             Ok(data) => data.is_dir(),
             _ => false,
         }
     }
     pub fn is_file(&self) -> bool {
-        match self.metadata() {
+        match self.stat() {
             Ok(data) => data.is_file(),
             _ => false,
         }
@@ -318,12 +462,12 @@ impl FileDir {
     }
 }
 
-fn to_file_dir(path: &str) -> Result<Box<dyn DFiles>> {
-    let data = metadata(path)?;
+fn to_file_dir(path: &str, backend: &Backend) -> Result<Box<dyn DFiles>> {
+    let data = backend.metadata(PathBuilder::from(path).as_path())?;
     if data.is_dir() {
-        Ok(Box::new(DirectoryInfo::open(path)))
+        Ok(Box::new(DirectoryInfo::open_in(path, backend.clone())))
     } else {
-        let f = FileInfo::open(path);
+        let f = FileInfo::open_in(path, backend.clone());
         Ok(Box::new(f))
     }
 }
@@ -334,6 +478,9 @@ fn already_exists_err() -> Error {
         "The file or directory already exists!",
     )
 }
+fn not_utf8_err() -> Error {
+    Error::new(ErrorKind::InvalidInput, "The path is not valid UTF-8!")
+}
 #[allow(unused)]
 fn not_found_err() -> Error {
     Error::new(ErrorKind::NotFound, "The file or directory is not found!")
@@ -1,19 +1,32 @@
+use super::backend::{Backend, FsBackend, OsBackend};
+use super::compress::{Compression, FinishWrite};
 use super::fd::FileDir;
 use super::stream::{FileReadStream, FileWriteStream};
 use super::Attributes;
 use super::DFiles;
 use crate::fs::pathstr::FileString;
 use crate::fs::pbuilder::PathBuilder;
-use crate::fs::stream::{BufferStream, Lines};
+use crate::fs::stream::{read_line_bytes, BufferStream, LineEnding, Lines, StreamReader};
 use crate::io::convert::ConvertBuffer;
+use crate::io::encoding::{DecodeReader, EncodeWriter, Encoding, TextOptions};
 use crate::io::{ConResult, ConvertError};
+use std::ffi::OsString;
 use std::fmt::Debug;
-use std::fs::{self, DirBuilder, File, OpenOptions};
-use std::io::{BufRead, BufReader, Error, ErrorKind, Read, Result, Write};
+use std::fs::{DirEntry, File};
+use std::io::{BufRead, BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 pub struct FileInfo {
     inner: PathBuilder,
     stream: Stream,
+    compression: Compression,
+    text: TextOptions,
+    line_ending: LineEnding,
+    backup: Option<String>,
+    buffer_size: usize,
+    durable: bool,
+    backend: Backend,
 }
 
 unsafe impl Send for FileInfo {}
@@ -23,6 +36,13 @@ impl Clone for FileInfo {
         FileInfo {
             inner: self.inner.clone(),
             stream: Stream::None,
+            compression: self.compression,
+            text: self.text,
+            line_ending: self.line_ending,
+            backup: self.backup.clone(),
+            buffer_size: self.buffer_size,
+            durable: self.durable,
+            backend: self.backend.clone(),
         }
     }
 }
@@ -33,7 +53,7 @@ impl warp::Reply for FileInfo {
         use url::form_urlencoded::byte_serialize;
         use warp::http::HeaderValue;
         use warp::hyper::header;
-        match std::fs::read(self.full_name()) {
+        match std::fs::read(self.as_path()) {
             Ok(buf) => {
                 let mut res = warp::reply::Response::new(buf.into());
                 res.headers_mut().insert(
@@ -64,13 +84,26 @@ impl From<File> for FileInfo {
         FileInfo {
             inner: unsafe { PathBuilder::from_uncheck(value) },
             stream: Stream::None,
+            compression: Compression::Auto,
+            text: TextOptions::default(),
+            line_ending: LineEnding::Lf,
+            backup: None,
+            buffer_size: BUFFER_SIZE,
+            durable: false,
+            backend: OsBackend::shared(),
         }
     }
 }
 
+/// The capacity of the write buffer, like `BufWriter`
+const BUFFER_SIZE: usize = 8 * 1024;
+/// Keeps the temporary names of concurrent `write_atomic` calls apart
+static ATOMIC_COUNT: AtomicUsize = AtomicUsize::new(0);
+
 enum Stream {
-    Write(File),
-    Read(BufReader<File>),
+    /// The writer and the line ending of `writeln`
+    Write(Box<dyn FinishWrite>, &'static str),
+    Read(StreamReader),
     None,
 }
 
@@ -90,7 +123,7 @@ impl PartialEq for FileInfo {
 
 impl DFiles for FileInfo {
     fn is_exist(&self) -> bool {
-        match self.metadata() {
+        match self.stat() {
             Ok(data) => data.is_file(),
             _ => false,
         }
@@ -112,11 +145,22 @@ impl DFiles for FileInfo {
         self.inner.mut_borrow()
     }
 
+    fn backend(&self) -> &dyn FsBackend {
+        &*self.backend
+    }
+
+    fn shared_backend(&self) -> Backend {
+        self.backend.clone()
+    }
+
     fn copy_new(&self, path: &str) -> Result<()> {
-        let builder = PathBuilder::from(path);
-        match builder.is_exist() {
+        self.copy_path(&PathBuilder::from(path))
+    }
+
+    fn copy_path(&self, path: &PathBuilder) -> Result<()> {
+        match self.backend.metadata(path.as_path()).is_ok() {
             false => {
-                fs::copy(self.full_name(), builder.full_name())?;
+                self.backend.copy(self.as_path(), path.as_path())?;
                 Ok(())
             }
             _ => Err(already_exists_err()),
@@ -124,36 +168,131 @@ impl DFiles for FileInfo {
     }
 
     fn move_new(&mut self, path: &str) -> Result<()> {
-        let builder = PathBuilder::from(path);
-        match builder.is_exist() {
+        self.move_path(&PathBuilder::from(path))
+    }
+
+    fn move_path(&mut self, path: &PathBuilder) -> Result<()> {
+        match self.backend.metadata(path.as_path()).is_ok() {
             false => {
-                fs::copy(self.full_name(), builder.full_name())?;
-                fs::remove_file(self.full_name())?;
-                *self = unsafe { FileInfo::open_uncheck(builder.full_name()) };
+                self.backend.copy(self.as_path(), path.as_path())?;
+                self.backend.remove_file(self.as_path())?;
+                *self = FileInfo::from_builder(path.clone(), self.backend.clone());
                 Ok(())
             }
             _ => Err(already_exists_err()),
         }
     }
     fn size_bytes(&self) -> u64 {
-        match self.metadata() {
-            Ok(data) => data.len(),
+        match self.stat() {
+            Ok(data) => data.len,
             _ => 0,
         }
     }
 }
 
+/// Serialized as the full path, deserialized with `OsBackend`
+#[cfg(feature = "serde")]
+impl serde::Serialize for FileInfo {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        self.builder().serialize(serializer)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for FileInfo {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        let path = PathBuilder::deserialize(deserializer)?;
+        Ok(FileInfo::from_builder(path, OsBackend::shared()))
+    }
+}
+
+impl From<&Path> for FileInfo {
+    fn from(value: &Path) -> Self {
+        FileInfo::from_path(value)
+    }
+}
+
+impl From<PathBuf> for FileInfo {
+    fn from(value: PathBuf) -> Self {
+        FileInfo::from_path(value)
+    }
+}
+
+impl From<DirEntry> for FileInfo {
+    fn from(value: DirEntry) -> Self {
+        FileInfo::from_path(value.path())
+    }
+}
+
+impl From<FileInfo> for PathBuf {
+    fn from(value: FileInfo) -> Self {
+        value.inner.into()
+    }
+}
+
+impl AsRef<Path> for FileInfo {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
 impl FileInfo {
     pub fn open<P: AsRef<str>>(value: P) -> Self {
+        FileInfo::open_in(value, OsBackend::shared())
+    }
+    pub fn open_in<P: AsRef<str>>(value: P, backend: Backend) -> Self {
         FileInfo {
             inner: PathBuilder::from(value),
             stream: Stream::None,
+            compression: Compression::Auto,
+            text: TextOptions::default(),
+            line_ending: LineEnding::Lf,
+            backup: None,
+            buffer_size: BUFFER_SIZE,
+            durable: false,
+            backend,
         }
     }
+    /// Open with `~`, `~user` and environment variables in `path` expanded
+    pub fn open_expanded<P: AsRef<str>>(path: P) -> Result<FileInfo> {
+        Ok(FileInfo::from_builder(
+            PathBuilder::expand(path)?,
+            OsBackend::shared(),
+        ))
+    }
+    pub fn from_path<P: AsRef<Path>>(path: P) -> FileInfo {
+        FileInfo::from_builder(PathBuilder::from_path(path), OsBackend::shared())
+    }
     pub unsafe fn open_uncheck<P: AsRef<str>>(path: P) -> FileInfo {
         FileInfo {
             inner: PathBuilder::from_uncheck(path.as_ref()),
             stream: Stream::None,
+            compression: Compression::Auto,
+            text: TextOptions::default(),
+            line_ending: LineEnding::Lf,
+            backup: None,
+            buffer_size: BUFFER_SIZE,
+            durable: false,
+            backend: OsBackend::shared(),
+        }
+    }
+
+    pub(crate) fn from_builder(inner: PathBuilder, backend: Backend) -> FileInfo {
+        FileInfo {
+            inner,
+            stream: Stream::None,
+            compression: Compression::Auto,
+            text: TextOptions::default(),
+            line_ending: LineEnding::Lf,
+            backup: None,
+            buffer_size: BUFFER_SIZE,
+            durable: false,
+            backend,
         }
     }
 
@@ -493,23 +632,29 @@ impl FileInfo {
         }
     }
     pub fn open_smart<P: AsRef<str>>(path: P) -> Result<FileInfo> {
-        let f = match File::open(path.as_ref()) {
-            Ok(f) => f,
+        FileInfo::open_smart_in(path, OsBackend::shared())
+    }
+    /// Open `path` in `backend`, creating the file and its parent directories when it does not exist
+    pub fn open_smart_in<P: AsRef<str>>(path: P, backend: Backend) -> Result<FileInfo> {
+        let f = FileInfo::open_in(path, backend);
+        match f.backend.metadata(f.as_path()) {
+            Ok(_) => (),
             Err(e) if e.kind() == ErrorKind::NotFound => {
-                let path = PathBuilder::from(path);
-                DirBuilder::new().recursive(true).create(path.parent())?;
-                File::create(path.full_name())?
+                if let Some(parent) = f.as_path().parent() {
+                    f.backend.create_dir_all(parent)?;
+                }
+                f.backend.open_write(f.as_path(), true)?;
             }
             Err(error) => return Err(error),
-        };
-        Ok(FileInfo::from(f))
+        }
+        Ok(f)
     }
 
     pub fn is_eq(&self, other: &Self) -> bool {
         fn is_eq(f: &FileInfo, other: &FileInfo) -> Result<bool> {
             if f.size_bytes() == other.size_bytes() {
-                let mut f = ReadFile::new(f.file()?);
-                let mut other = ReadFile::new(other.file()?);
+                let mut f = ReadFile::new(f.file()?, f.size_bytes());
+                let mut other = ReadFile::new(other.file()?, other.size_bytes());
                 loop {
                     let Some(f_read) = f.read() else { return Ok(true)};
                     let Some(other_read) = other.read() else { return Ok(false)};
@@ -526,7 +671,7 @@ impl FileInfo {
     }
 
     pub fn del(self) -> Result<()> {
-        fs::remove_file(self.full_name())
+        self.backend.remove_file(self.as_path())
     }
 
     pub fn extension(&self) -> &str {
@@ -535,10 +680,181 @@ impl FileInfo {
     pub fn set_extension(&mut self, extension: &str) -> Result<()> {
         let pre_path = self.inner.clone();
         self.inner.set_extension(extension);
-        fs::rename(pre_path.full_name(), self.full_name())
+        self.backend.rename(pre_path.as_path(), self.as_path())
+    }
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+    /// Set the compression used by the next `start_reading`, `start_writing` or `overwrite`
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.compression = compression;
+    }
+    /// Replace the contents by writing a temporary file next to it and renaming it over the file,
+    /// readers see either the old or the new contents
+    pub fn write_atomic<T: BufferStream>(&self, contents: T) -> Result<()> {
+        let mut name = OsString::from(".");
+        name.push(self.inner.os_name());
+        name.push(format!(
+            ".{}.{}.tmp",
+            std::process::id(),
+            ATOMIC_COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        let tmp = self.as_path().with_file_name(name);
+        let write = || -> Result<()> {
+            let mut f = self.encoder(self.backend.open_write(&tmp, false)?, true)?;
+            f.write_all(contents.write_buf().as_buf())?;
+            f.finish()?;
+            if self.backend.metadata(self.as_path()).is_ok() {
+                self.backend.copy_permissions(self.as_path(), &tmp)?;
+            }
+            match self.durable {
+                true => self.backend.sync(&tmp, false),
+                false => Ok(()),
+            }
+        };
+        match write().and_then(|_| self.backend.rename(&tmp, self.as_path())) {
+            Ok(()) => Ok(()),
+            Err(e) => {
+                let _ = self.backend.remove_file(&tmp);
+                Err(e)
+            }
+        }
+    }
+    pub fn encoding(&self) -> Option<Encoding> {
+        self.text.encoding
+    }
+    /// Set the encoding that reads are decoded from and writes are encoded to,
+    /// `None` reads and writes the bytes unchanged.
+    /// A byte order mark at the start of the file overrides it when reading.
+    pub fn set_encoding(&mut self, encoding: Option<Encoding>) {
+        self.text.encoding = encoding;
+    }
+    /// Replace invalid sequences with U+FFFD when reading and characters the encoding
+    /// can not hold with `?` when writing, reads are decoded as UTF-8 when no encoding is set
+    pub fn set_lossy(&mut self, lossy: bool) {
+        self.text.lossy = lossy;
+    }
+    /// Write the byte order mark of the encoding when writing to an empty file
+    pub fn set_bom(&mut self, bom: bool) {
+        self.text.bom = bom;
+    }
+    pub fn backup(&self) -> Option<&str> {
+        self.backup.as_deref()
+    }
+    /// Keep the old contents as the path plus `suffix` when `edit_lines`, `replace_all`,
+    /// `insert_line` or `delete_line` rewrite the file
+    pub fn set_backup(&mut self, suffix: Option<&str>) {
+        self.backup = suffix.map(|suffix| suffix.to_string());
+    }
+    pub fn buffer_size(&self) -> usize {
+        self.buffer_size
+    }
+    /// Set the capacity of the write buffer from the next `start_writing` or `overwrite`,
+    /// 8 KB by default and 0 to write straight through
+    pub fn set_buffer_size(&mut self, buffer_size: usize) {
+        self.buffer_size = buffer_size;
+    }
+    pub fn durable(&self) -> bool {
+        self.durable
     }
-    fn file(&self) -> Result<File> {
-        File::open(self.full_name())
+    /// Sync the data to the disk after every `writeln` and `close`, and before
+    /// `write_atomic` renames the new contents over the file
+    pub fn set_durable(&mut self, durable: bool) {
+        self.durable = durable;
+    }
+    pub fn line_ending(&self) -> LineEnding {
+        self.line_ending
+    }
+    /// Set the line ending `writeln` writes from the next `start_writing` or `overwrite`, `Lf` by default
+    pub fn set_line_ending(&mut self, line_ending: LineEnding) {
+        self.line_ending = line_ending;
+    }
+    /// Rewrite the file with every line ending as `line_ending` using `write_atomic`,
+    /// `Preserve` uses the line ending the file has most
+    pub fn normalize_line_endings(&self, line_ending: LineEnding) -> Result<()> {
+        let mut buf = Vec::new();
+        self.reader()?.read_to_end(&mut buf)?;
+        let eol = match line_ending {
+            LineEnding::Preserve => LineEnding::detect(&buf).unwrap_or(LineEnding::Native),
+            line_ending => line_ending,
+        };
+        let mut reader = buf.as_slice();
+        let mut out = Vec::with_capacity(buf.len());
+        let mut line = Vec::new();
+        loop {
+            line.clear();
+            let n = read_line_bytes(&mut reader, &mut line)?;
+            if n == 0 {
+                break;
+            }
+            out.extend_from_slice(&line);
+            if n > line.len() {
+                out.extend_from_slice(eol.as_str().as_bytes());
+            }
+        }
+        self.write_atomic(out)
+    }
+    /// Guess the encoding from the first 4 KB of the file with `Encoding::detect`
+    pub fn detect_encoding(&self) -> Result<Encoding> {
+        let mut buf = Vec::new();
+        let mut f = self.clone();
+        f.text = TextOptions::default();
+        f.reader()?.take(4096).read_to_end(&mut buf)?;
+        Ok(Encoding::detect(&buf))
+    }
+    /// The line ending of `writeln`, read from the file for `Preserve`
+    fn eol(&self) -> Result<&'static str> {
+        if self.line_ending != LineEnding::Preserve {
+            return Ok(self.line_ending.as_str());
+        }
+        let mut buf = Vec::new();
+        if self.is_exist() {
+            self.reader()?.take(4096).read_to_end(&mut buf)?;
+        }
+        Ok(LineEnding::detect(&buf)
+            .unwrap_or(LineEnding::Native)
+            .as_str())
+    }
+    pub(crate) fn reader(&self) -> Result<StreamReader> {
+        let mut f = BufReader::new(self.file()?);
+        let compression = self.compression.for_reading(&mut f, self.extension())?;
+        let f = compression.decoder(f)?;
+        Ok(BufReader::new(match self.text {
+            TextOptions {
+                encoding: None,
+                lossy: false,
+                ..
+            } => f,
+            TextOptions {
+                encoding, lossy, ..
+            } => Box::new(DecodeReader::new(
+                f,
+                encoding.unwrap_or(Encoding::Utf8),
+                lossy,
+            )),
+        }))
+    }
+    fn writer(&self, append: bool) -> Result<Box<dyn FinishWrite>> {
+        let empty = !append || self.size_bytes() == 0;
+        self.encoder(self.backend.open_write(self.as_path(), append)?, empty)
+    }
+    /// Buffer, compress and encode the writes to `f`, starting with a byte order mark when `empty`
+    fn encoder(&self, f: Box<dyn Write + Send>, empty: bool) -> Result<Box<dyn FinishWrite>> {
+        let f = BufWriter::with_capacity(self.buffer_size, f);
+        let f = self.compression.for_writing(self.extension()).encoder(f)?;
+        match self.text.encoding {
+            Some(Encoding::Utf8) if !self.text.bom => Ok(f),
+            Some(encoding) => Ok(Box::new(EncodeWriter::new(
+                f,
+                encoding,
+                self.text.lossy,
+                self.text.bom && empty,
+            )?)),
+            None => Ok(f),
+        }
+    }
+    fn file(&self) -> Result<Box<dyn Read + Send>> {
+        self.backend.open_read(self.as_path())
     }
     pub fn to_file_dir(self) -> FileDir {
         FileDir::from(self)
@@ -555,14 +871,15 @@ impl FileInfo {
 
 impl FileWriteStream for FileInfo {
     fn start_writing(&mut self) -> Result<()> {
-        let f = append_file(self.full_name())?;
-        self.stream = Stream::Write(f);
+        self.close()?;
+        self.stat()?;
+        self.stream = Stream::Write(self.writer(true)?, self.eol()?);
         Ok(())
     }
 
     fn write<T: BufferStream>(&mut self, contents: T) -> Result<()> {
         match &mut self.stream {
-            Stream::Write(f) => {
+            Stream::Write(f, _) => {
                 let write_buf = contents.write_buf();
                 f.write_all(write_buf.as_buf())
             }
@@ -572,26 +889,61 @@ impl FileWriteStream for FileInfo {
 
     fn writeln<T: BufferStream>(&mut self, contents: T) -> Result<()> {
         match &mut self.stream {
-            Stream::Write(f) => {
+            Stream::Write(f, eol) => {
                 f.write_all(contents.write_buf().as_buf())?;
-                f.write_all(&[b'\n'])
+                f.write_all(eol.as_bytes())?;
             }
-            _ => Err(self.error(true).into()),
+            _ => return Err(self.error(true).into()),
+        }
+        match self.durable {
+            true => self.sync_data(),
+            false => Ok(()),
         }
     }
 
     fn overwrite<T: BufferStream>(&mut self, contents: T) -> Result<()> {
-        let mut f = File::create(self.full_name())?;
+        self.close()?;
+        let eol = self.eol()?;
+        let mut f = self.writer(false)?;
         f.write_all(contents.write_buf().as_buf())?;
-        self.stream = Stream::Write(f);
+        self.stream = Stream::Write(f, eol);
         Ok(())
     }
+
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.stream {
+            Stream::Write(f, _) => f.flush(),
+            _ => Ok(()),
+        }
+    }
+
+    fn sync_data(&mut self) -> Result<()> {
+        self.flush()?;
+        self.backend.sync(self.as_path(), false)
+    }
+
+    fn sync_all(&mut self) -> Result<()> {
+        self.flush()?;
+        self.backend.sync(self.as_path(), true)
+    }
+
+    fn close(&mut self) -> Result<()> {
+        let Stream::Write(mut f, _) = std::mem::replace(&mut self.stream, Stream::None) else {
+            return Ok(());
+        };
+        f.finish()?;
+        drop(f);
+        match self.durable {
+            true => self.backend.sync(self.as_path(), false),
+            false => Ok(()),
+        }
+    }
 }
 
 impl FileReadStream for FileInfo {
     fn start_reading(&mut self) -> Result<()> {
-        let f = File::open(self.full_name())?;
-        self.stream = Stream::Read(BufReader::new(f));
+        self.close()?;
+        self.stream = Stream::Read(self.reader()?);
         Ok(())
     }
 
@@ -601,13 +953,10 @@ impl FileReadStream for FileInfo {
             _ => Err(self.error(false).into()),
         }
     }
-    fn lines(&self) -> ConResult<Lines<BufReader<File>>> {
+    fn lines(&self) -> ConResult<Lines<StreamReader>> {
         match self.stream {
-            Stream::Read(_) => match File::open(self.full_name()) {
-                Ok(f) => {
-                    let reader = BufReader::new(f);
-                    Ok(Lines::new(reader))
-                }
+            Stream::Read(_) => match self.reader() {
+                Ok(reader) => Ok(Lines::new(reader)),
                 _ => Err(self.error(false)),
             },
             _ => Err(self.error(false)),
@@ -642,24 +991,45 @@ impl FileReadStream for FileInfo {
         match &mut self.stream {
             Stream::Read(reader) => {
                 let mut buf = Vec::new();
-                reader.read_until(byte, &mut buf)?;
-                if buf.is_empty() {
+                if reader.read_until(byte, &mut buf)? == 0 {
                     return Err(ConvertError::Empty);
                 }
                 if buf.ends_with(&[byte]) {
                     buf.pop();
-                    if byte == b'\n' && buf.ends_with(&[b'\r']) {
+                    if byte == b'\n' && buf.ends_with(b"\r") {
                         buf.pop();
                     }
                 }
                 B::from_buf(buf)
             }
-            _ => Err(self.error(false).into()),
+            _ => Err(self.error(false)),
+        }
+    }
+
+    fn read_until_raw(&mut self, byte: u8) -> ConResult<Vec<u8>> {
+        match &mut self.stream {
+            Stream::Read(reader) => {
+                let mut buf = Vec::new();
+                if reader.read_until(byte, &mut buf)? == 0 {
+                    return Err(ConvertError::Empty);
+                }
+                Ok(buf)
+            }
+            _ => Err(self.error(false)),
         }
     }
 
     fn read_line<B: ConvertBuffer>(&mut self) -> ConResult<B> {
-        self.read_until(b'\n')
+        match &mut self.stream {
+            Stream::Read(reader) => {
+                let mut buf = Vec::new();
+                if read_line_bytes(reader, &mut buf)? == 0 {
+                    return Err(ConvertError::Empty);
+                }
+                B::from_buf(buf)
+            }
+            _ => Err(self.error(false)),
+        }
     }
 }
 pub fn read_first_line(path: &str) -> Result<String> {
@@ -686,26 +1056,15 @@ fn already_exists_err() -> Error {
     Error::new(ErrorKind::AlreadyExists, "The file already exists!")
 }
 
-fn append_file(path: &str) -> Result<File> {
-    match OpenOptions::new().append(true).open(path) {
-        Ok(f) => Ok(f),
-        _ => File::open(path),
-    }
-}
-
 struct ReadFile {
-    file: File,
+    file: Box<dyn Read + Send>,
     end: bool,
     count: u64,
     pos: u64,
 }
 
 impl ReadFile {
-    pub fn new(file: File) -> ReadFile {
-        let len = match file.metadata() {
-            Ok(data) => data.len(),
-            _ => 0,
-        };
+    pub fn new(file: Box<dyn Read + Send>, len: u64) -> ReadFile {
         let count = len / 131_072;
         ReadFile {
             file,
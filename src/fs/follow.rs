@@ -0,0 +1,171 @@
+use super::backend::os_only_err;
+use super::{DFiles, FileInfo};
+use std::fs::{self, File};
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::thread;
+use std::time::Duration;
+
+/// Follows a growing file like `tail -f`, yielding each line once it is complete.
+///
+/// When the file shrinks it is read again from the start, and when the path
+/// points to a new file (log rotation) the rest of the old file is read first.
+/// `offset` is the position after the last yielded line and can be saved to
+/// resume with `FileInfo::follow_from`.
+pub struct Follow {
+    path: PathBuf,
+    file: Option<File>,
+    id: Option<(u64, u64)>,
+    offset: u64,
+    pending: Vec<u8>,
+    interval: Duration,
+}
+
+impl FileInfo {
+    /// Follow the lines appended after the current end of the file
+    pub fn follow(&self) -> Result<Follow> {
+        let len = self.backend().metadata(self.as_path())?.len;
+        self.follow_from(len)
+    }
+    /// Follow the file from the byte `offset`, from the start when the file is now shorter.
+    /// Only files of the operating system can be followed.
+    pub fn follow_from(&self, offset: u64) -> Result<Follow> {
+        if !self.backend().is_os() {
+            return Err(os_only_err("Following a file"));
+        }
+        let mut follow = Follow {
+            path: self.as_path().to_path_buf(),
+            file: None,
+            id: None,
+            offset: 0,
+            pending: Vec::new(),
+            interval: Duration::from_millis(500),
+        };
+        follow.open(offset)?;
+        Ok(follow)
+    }
+}
+
+impl Follow {
+    /// How long `next` sleeps between checks for new lines, 500 ms by default
+    pub fn interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+    /// The byte offset after the last line yielded
+    pub fn offset(&self) -> u64 {
+        self.offset
+    }
+    /// The next complete line without waiting, `None` when there is none yet
+    pub fn poll_line(&mut self) -> Option<Result<String>> {
+        if let Some(line) = self.take_line() {
+            return Some(line);
+        }
+        if let Err(e) = self.read_available() {
+            return Some(Err(e));
+        }
+        if let Some(line) = self.take_line() {
+            return Some(line);
+        }
+        match self.check_replaced() {
+            Ok(Some(rest)) => Some(Ok(rest)),
+            Ok(None) => self.take_line(),
+            Err(e) => Some(Err(e)),
+        }
+    }
+
+    fn open(&mut self, offset: u64) -> Result<()> {
+        let mut file = File::open(&self.path)?;
+        let data = file.metadata()?;
+        let offset = if data.len() < offset { 0 } else { offset };
+        file.seek(SeekFrom::Start(offset))?;
+        self.id = file_id(&data);
+        self.file = Some(file);
+        self.offset = offset;
+        self.pending.clear();
+        Ok(())
+    }
+
+    fn read_available(&mut self) -> Result<()> {
+        if self.file.is_none() {
+            match self.open(0) {
+                Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+                result => result?,
+            }
+        }
+        if let Some(file) = &mut self.file {
+            file.read_to_end(&mut self.pending)?;
+        }
+        Ok(())
+    }
+
+    /// Start over when the file was truncated or replaced, returning the unterminated
+    /// last line of a replaced file
+    fn check_replaced(&mut self) -> Result<Option<String>> {
+        let data = match fs::metadata(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        if self.file.is_some() && file_id(&data) != self.id {
+            let rest = std::mem::take(&mut self.pending);
+            self.open(0)?;
+            self.read_available()?;
+            return match rest.is_empty() {
+                true => Ok(None),
+                false => utf8(rest).map(Some),
+            };
+        }
+        if data.len() < self.offset + self.pending.len() as u64 {
+            self.open(0)?;
+            self.read_available()?;
+        }
+        Ok(None)
+    }
+
+    fn take_line(&mut self) -> Option<Result<String>> {
+        let end = self
+            .pending
+            .iter()
+            .position(|b| *b == b'\n' || *b == b'\r')?;
+        let len = match (self.pending[end], self.pending.get(end + 1)) {
+            (b'\r', Some(b'\n')) => end + 2,
+            (b'\r', None) => return None,
+            _ => end + 1,
+        };
+        let mut line: Vec<u8> = self.pending.drain(..len).collect();
+        line.truncate(end);
+        self.offset += len as u64;
+        Some(utf8(line))
+    }
+}
+
+impl Iterator for Follow {
+    type Item = Result<String>;
+
+    /// Wait for the next complete line
+    fn next(&mut self) -> Option<Result<String>> {
+        loop {
+            match self.poll_line() {
+                Some(line) => return Some(line),
+                None => thread::sleep(self.interval),
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+fn file_id(data: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((data.dev(), data.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_id(_data: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+fn utf8(line: Vec<u8>) -> Result<String> {
+    String::from_utf8(line)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+}
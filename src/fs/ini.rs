@@ -0,0 +1,217 @@
+use super::stream::FileReadStream;
+use super::FileInfo;
+use crate::io::{ConResult, ConvertBuffer, ConvertError};
+use std::fmt::{Display, Formatter};
+use std::io::Result;
+use std::ops::Range;
+
+/// An INI document that keeps its text, so editing a key only touches the value
+/// and comments, ordering and whitespace are written back unchanged.
+///
+/// Sections and keys are compared ignoring ASCII case, keys before the first
+/// section belong to the section `""`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Ini {
+    lines: Vec<String>,
+    newline: &'static str,
+}
+
+enum IniLine {
+    Section(String),
+    Entry {
+        key: Range<usize>,
+        value: Range<usize>,
+    },
+    Other,
+}
+
+impl FileInfo {
+    pub fn read_ini(&self) -> Result<Ini> {
+        let mut f = self.clone();
+        f.start_reading()?;
+        Ok(Ini::parse(&f.read_to_string()?))
+    }
+    /// Write `ini` back with `write_atomic`
+    pub fn write_ini(&self, ini: &Ini) -> Result<()> {
+        self.write_atomic(ini.to_string())
+    }
+}
+
+impl Ini {
+    pub fn parse(text: &str) -> Ini {
+        let newline = match text.contains("\r\n") {
+            true => "\r\n",
+            false => "\n",
+        };
+        Ini {
+            lines: text
+                .split('\n')
+                .map(|line| line.strip_suffix('\r').unwrap_or(line).to_string())
+                .collect(),
+            newline,
+        }
+    }
+    pub fn sections(&self) -> Vec<&str> {
+        let mut sections = Vec::new();
+        for line in &self.lines {
+            if let IniLine::Section(_) = parse_line(line) {
+                let name = section_name(line);
+                if !sections.iter().any(|s: &&str| s.eq_ignore_ascii_case(name)) {
+                    sections.push(name);
+                }
+            }
+        }
+        sections
+    }
+    pub fn keys(&self, section: &str) -> Vec<&str> {
+        self.entries(section)
+            .into_iter()
+            .map(|(i, key, _)| &self.lines[i][key])
+            .collect()
+    }
+    pub fn contains(&self, section: &str, key: &str) -> bool {
+        self.find(section, key).is_some()
+    }
+    /// The value of `key` without quotes and inline comment
+    pub fn get(&self, section: &str, key: &str) -> Option<&str> {
+        let (i, value) = self.find(section, key)?;
+        Some(unquote(&self.lines[i][value]))
+    }
+    pub fn get_as<B: ConvertBuffer>(&self, section: &str, key: &str) -> ConResult<B> {
+        match self.get(section, key) {
+            Some(value) => B::from_buf(value.as_bytes().to_vec()),
+            None => Err(ConvertError::Empty),
+        }
+    }
+    /// Set `key` in place, or add it at the end of its section, adding the section when needed
+    pub fn set<V: Display>(&mut self, section: &str, key: &str, value: V) {
+        let value = value.to_string();
+        if let Some((i, mut range)) = self.find(section, key) {
+            if unquote(&self.lines[i][range.clone()]).len() + 2 == range.len() {
+                range = range.start + 1..range.end - 1;
+            }
+            self.lines[i].replace_range(range, &value);
+            return;
+        }
+        let entry = format!("{} = {}", key, value);
+        match self.section_end(section) {
+            Some(end) => self.lines.insert(end, entry),
+            None => {
+                let mut end = self.lines.len();
+                if self.lines.last().is_some_and(|line| line.is_empty()) {
+                    end -= 1;
+                }
+                let mut added = vec![format!("[{}]", section), entry];
+                if end > 0 && !self.lines[end - 1].trim().is_empty() {
+                    added.insert(0, String::new());
+                }
+                self.lines.splice(end..end, added);
+            }
+        }
+    }
+    /// Remove `key`, returns whether it existed
+    pub fn remove(&mut self, section: &str, key: &str) -> bool {
+        match self.find(section, key) {
+            Some((i, _)) => {
+                self.lines.remove(i);
+                true
+            }
+            None => false,
+        }
+    }
+
+    fn entries(&self, section: &str) -> Vec<(usize, Range<usize>, Range<usize>)> {
+        let mut entries = Vec::new();
+        let mut current = section.is_empty();
+        for (i, line) in self.lines.iter().enumerate() {
+            match parse_line(line) {
+                IniLine::Section(name) => current = name.eq_ignore_ascii_case(section),
+                IniLine::Entry { key, value } if current => entries.push((i, key, value)),
+                _ => {}
+            }
+        }
+        entries
+    }
+    fn find(&self, section: &str, key: &str) -> Option<(usize, Range<usize>)> {
+        self.entries(section)
+            .into_iter()
+            .find(|(i, range, _)| self.lines[*i][range.clone()].eq_ignore_ascii_case(key))
+            .map(|(i, _, value)| (i, value))
+    }
+    /// The line after the last entry of the last `section`, or after its header
+    fn section_end(&self, section: &str) -> Option<usize> {
+        let mut end = match section.is_empty() {
+            true => Some(0),
+            false => None,
+        };
+        let mut current = section.is_empty();
+        for (i, line) in self.lines.iter().enumerate() {
+            match parse_line(line) {
+                IniLine::Section(name) => {
+                    current = name.eq_ignore_ascii_case(section);
+                    if current {
+                        end = Some(i + 1);
+                    }
+                }
+                IniLine::Entry { .. } if current => end = Some(i + 1),
+                _ => {}
+            }
+        }
+        end
+    }
+}
+
+impl Display for Ini {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.lines.join(self.newline))
+    }
+}
+
+fn parse_line(line: &str) -> IniLine {
+    let trimmed = line.trim();
+    if trimmed.is_empty() || trimmed.starts_with(';') || trimmed.starts_with('#') {
+        return IniLine::Other;
+    }
+    if trimmed.starts_with('[') && trimmed.contains(']') {
+        return IniLine::Section(section_name(line).to_string());
+    }
+    let Some(eq) = line.find(['=', ':']) else {
+        return IniLine::Other;
+    };
+    let key_start = line.len() - line.trim_start().len();
+    let key_end = line[..eq].trim_end().len();
+    let rest = &line[eq + 1..];
+    let value_start = eq + 1 + (rest.len() - rest.trim_start().len());
+    let mut value_end = line.len();
+    if !line[value_start..].starts_with('"') {
+        for (i, ch) in line[value_start..].char_indices() {
+            let prev = line[..value_start + i].chars().last();
+            if (ch == ';' || ch == '#') && prev.is_some_and(char::is_whitespace) {
+                value_end = value_start + i;
+                break;
+            }
+        }
+    } else if let Some(close) = line[value_start + 1..].find('"') {
+        value_end = value_start + close + 2;
+    }
+    value_end = value_start + line[value_start..value_end].trim_end().len();
+    IniLine::Entry {
+        key: key_start..key_end,
+        value: value_start..value_end,
+    }
+}
+
+fn section_name(line: &str) -> &str {
+    let line = line.trim();
+    match line.find(']') {
+        Some(end) => line[1..end].trim(),
+        None => "",
+    }
+}
+
+fn unquote(value: &str) -> &str {
+    match value.len() >= 2 && value.starts_with('"') && value.ends_with('"') {
+        true => &value[1..value.len() - 1],
+        false => value,
+    }
+}
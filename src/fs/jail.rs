@@ -0,0 +1,350 @@
+use super::backend::os_only_err;
+use super::fd::FileDir;
+use super::pbuilder::PathBuilder;
+use super::{DFiles, DirectoryInfo, FileInfo};
+use std::ffi::OsString;
+use std::fs::{self, symlink_metadata, File};
+use std::io::{Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+
+const MAX_LINKS: usize = 40;
+
+/// A view of a directory that only resolves paths strictly inside of it.
+///
+/// Paths are relative to the root. `..` that would leave the root, absolute
+/// paths and symlinks that are absolute or point outside of the root are
+/// rejected with `ErrorKind::PermissionDenied`.
+///
+/// On Linux `open_file`, `create_file`, `create_dir` and `children` work through the
+/// root with `openat2(RESOLVE_BENEATH)` when it is available, so the kernel resolves
+/// the path and a symlink swapped in meanwhile can not escape. The paths returned by
+/// `resolve`, `file` and `dir` are only checked when they are returned.
+/// A root on another backend than `OsBackend` goes through that backend, which has no symlinks.
+#[derive(Clone, Debug)]
+pub struct Jail {
+    root: DirectoryInfo,
+}
+
+impl DirectoryInfo {
+    pub fn jail(&self) -> Jail {
+        Jail { root: self.clone() }
+    }
+}
+
+impl Jail {
+    pub fn root(&self) -> &DirectoryInfo {
+        &self.root
+    }
+
+    /// Resolve `path` below the root and return the full path.
+    /// Components that do not exist yet are kept as they are.
+    pub fn resolve(&self, path: &str) -> Result<PathBuilder> {
+        let path = relative(path)?;
+        #[cfg(target_os = "linux")]
+        if self.is_os() {
+            match beneath::open(self.root.as_path(), Path::new(&path), libc::O_PATH) {
+                Some(Err(e)) if e.kind() == ErrorKind::PermissionDenied => return Err(e),
+                _ => (),
+            }
+        }
+        let resolved = resolve(self.root.as_path(), &path, self.is_os())?;
+        Ok(PathBuilder::from_path(resolved))
+    }
+
+    pub fn file(&self, path: &str) -> Result<FileInfo> {
+        let path = self.resolve(path)?;
+        Ok(FileInfo::from_builder(path, self.root.shared_backend()))
+    }
+
+    pub fn dir(&self, path: &str) -> Result<DirectoryInfo> {
+        let path = self.resolve(path)?;
+        Ok(DirectoryInfo::from_builder(
+            path,
+            self.root.shared_backend(),
+        ))
+    }
+
+    /// Open an existing file for reading, without a window between the check and the open on Linux.
+    /// Only a root on `OsBackend` has files to open.
+    pub fn open_file(&self, path: &str) -> Result<File> {
+        if !self.is_os() {
+            return Err(os_only_err("Opening a jailed file"));
+        }
+        #[cfg(target_os = "linux")]
+        if let Some(f) = beneath::open(
+            self.root.as_path(),
+            Path::new(&relative(path)?),
+            libc::O_RDONLY,
+        ) {
+            return f;
+        }
+        File::open(self.resolve(path)?.as_path())
+    }
+
+    /// Create the file and its parent directories when they do not exist
+    pub fn create_file(&self, path: &str) -> Result<FileInfo> {
+        #[cfg(target_os = "linux")]
+        if self.is_os() {
+            if let Some(created) =
+                beneath::create_file(self.root.as_path(), Path::new(&relative(path)?))
+            {
+                created?;
+                return self.file(path);
+            }
+        }
+        let f = self.file(path)?;
+        if let Some(parent) = f.as_path().parent() {
+            self.root.backend().create_dir_all(parent)?;
+        }
+        if !f.is_exist() {
+            self.root.backend().open_write(f.as_path(), true)?;
+        }
+        Ok(f)
+    }
+
+    /// Create the directory and its parents when they do not exist
+    pub fn create_dir(&self, path: &str) -> Result<DirectoryInfo> {
+        #[cfg(target_os = "linux")]
+        if self.is_os() {
+            if let Some(created) =
+                beneath::create_dir_all(self.root.as_path(), Path::new(&relative(path)?))
+            {
+                created?;
+                return self.dir(path);
+            }
+        }
+        let mut dir = self.dir(path)?;
+        dir.create()?;
+        Ok(dir)
+    }
+
+    /// The entries of the directory, leaving out the symlinks that point outside of the root
+    pub fn children(&self, path: &str) -> Result<Vec<FileDir>> {
+        let dir = self.dir(path)?;
+        let rel = relative(path)?;
+        #[cfg(target_os = "linux")]
+        let names = match self
+            .is_os()
+            .then(|| beneath::read_dir(self.root.as_path(), Path::new(&rel)))
+            .flatten()
+        {
+            Some(names) => names?,
+            None => read_names(&dir, path)?,
+        };
+        #[cfg(not(target_os = "linux"))]
+        let names = read_names(&dir, path)?;
+        let mut children = Vec::new();
+        for name in names {
+            let child = Path::new(&rel).join(&name);
+            if self.escapes(&child) {
+                continue;
+            }
+            let path = PathBuilder::from_path(dir.as_path().join(&name));
+            if let Ok(child) = FileDir::open_path_in(path, self.root.shared_backend()) {
+                children.push(child);
+            }
+        }
+        Ok(children)
+    }
+
+    /// Whether the entry at `rel` is a symlink that resolves outside of the root
+    fn escapes(&self, rel: &Path) -> bool {
+        #[cfg(target_os = "linux")]
+        if self.is_os() {
+            if let Some(result) = beneath::open(self.root.as_path(), rel, libc::O_PATH) {
+                return matches!(result, Err(e) if e.kind() == ErrorKind::PermissionDenied);
+            }
+        }
+        matches!(
+            resolve(self.root.as_path(), &rel.to_string_lossy(), self.is_os()),
+            Err(e) if e.kind() == ErrorKind::PermissionDenied
+        )
+    }
+
+    /// Whether the root is on the disk, the other backends have no symlinks
+    fn is_os(&self) -> bool {
+        self.root.backend().is_os()
+    }
+}
+
+/// `path` with `/` separators, rejecting absolute paths
+fn relative(path: &str) -> Result<String> {
+    let path = path.replace("\\", "/");
+    let bytes = path.as_bytes();
+    if path.starts_with('/') || (bytes.len() > 1 && bytes[1] == b':') {
+        return Err(escape_err(&path));
+    }
+    Ok(path)
+}
+
+fn read_names(dir: &DirectoryInfo, path: &str) -> Result<Vec<OsString>> {
+    if !dir.is_exist() {
+        return Err(Error::new(
+            ErrorKind::NotFound,
+            format!("The directory '{}' cannot be found!", path),
+        ));
+    }
+    let paths = dir.backend().read_dir(dir.as_path())?;
+    Ok(paths
+        .iter()
+        .filter_map(|path| path.file_name().map(|name| name.to_os_string()))
+        .collect())
+}
+
+/// Walk `path` one component at a time below `root`, following symlinks on the disk
+/// the same way `RESOLVE_BENEATH` does
+fn resolve(root: &Path, path: &str, os: bool) -> Result<PathBuf> {
+    let mut resolved: Vec<String> = Vec::new();
+    let mut pending: Vec<String> = path.split('/').rev().map(String::from).collect();
+    let mut links = 0;
+    while let Some(part) = pending.pop() {
+        match part.as_str() {
+            "" | "." => (),
+            ".." => {
+                if resolved.pop().is_none() {
+                    return Err(escape_err(path));
+                }
+            }
+            _ => {
+                let full = root.join(resolved.join("/")).join(&part);
+                let data = match os {
+                    true => symlink_metadata(&full).ok(),
+                    false => None,
+                };
+                match data {
+                    Some(data) if data.is_symlink() => {
+                        links += 1;
+                        if links > MAX_LINKS {
+                            return Err(Error::new(
+                                ErrorKind::InvalidInput,
+                                "Too many levels of symbolic links!",
+                            ));
+                        }
+                        let link = fs::read_link(&full)?.display().to_string();
+                        let link = link.replace("\\", "/");
+                        if link.starts_with('/') || link.as_bytes().get(1) == Some(&b':') {
+                            return Err(escape_err(path));
+                        }
+                        pending.extend(link.split('/').rev().map(String::from));
+                    }
+                    _ => resolved.push(part),
+                }
+            }
+        }
+    }
+    Ok(root.join(resolved.join("/")))
+}
+
+#[cfg(target_os = "linux")]
+mod beneath {
+    use super::escape_err;
+    use std::ffi::{CString, OsString};
+    use std::fs::{self, File};
+    use std::io::{Error, ErrorKind, Result};
+    use std::os::fd::{AsRawFd, FromRawFd};
+    use std::os::unix::ffi::OsStrExt;
+    use std::path::{Component, Path, PathBuf};
+
+    /// `openat2` with `RESOLVE_BENEATH`, `None` when the kernel does not support it
+    pub fn open(root: &Path, path: &Path, flags: libc::c_int) -> Option<Result<File>> {
+        open_mode(root, path, flags, 0)
+    }
+
+    /// Create `path` when it does not exist and open it for writing
+    pub fn create_file(root: &Path, path: &Path) -> Option<Result<()>> {
+        if let Some(parent) = path.parent() {
+            if let Err(e) = create_dir_all(root, parent)? {
+                return Some(Err(e));
+            }
+        }
+        let flags = libc::O_WRONLY | libc::O_CREAT;
+        Some(open_mode(root, path, flags, 0o666)?.map(drop))
+    }
+
+    /// `mkdirat` every missing directory of `path` in its parent, which is opened beneath `root`
+    pub fn create_dir_all(root: &Path, path: &Path) -> Option<Result<()>> {
+        let dir_flags = libc::O_PATH | libc::O_DIRECTORY;
+        let mut parent = PathBuf::new();
+        for component in path.components() {
+            match component {
+                Component::Normal(name) => {
+                    let dir = match open(root, &parent, dir_flags)? {
+                        Ok(dir) => dir,
+                        Err(e) => return Some(Err(e)),
+                    };
+                    let Ok(name_c) = CString::new(name.as_bytes()) else {
+                        return Some(Err(Error::from(ErrorKind::InvalidInput)));
+                    };
+                    if unsafe { libc::mkdirat(dir.as_raw_fd(), name_c.as_ptr(), 0o777) } < 0 {
+                        let error = Error::last_os_error();
+                        if error.raw_os_error() != Some(libc::EEXIST) {
+                            return Some(Err(error));
+                        }
+                    }
+                    parent.push(name);
+                }
+                Component::ParentDir => parent.push(".."),
+                _ => (),
+            }
+        }
+        Some(open(root, path, dir_flags)?.map(drop))
+    }
+
+    /// The names in the directory `path`, opened beneath `root` and read through `/proc`
+    pub fn read_dir(root: &Path, path: &Path) -> Option<Result<Vec<OsString>>> {
+        let dir = match open(root, path, libc::O_RDONLY | libc::O_DIRECTORY)? {
+            Ok(dir) => dir,
+            Err(e) => return Some(Err(e)),
+        };
+        let entries = match fs::read_dir(format!("/proc/self/fd/{}", dir.as_raw_fd())) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => return None,
+            Err(e) => return Some(Err(e)),
+        };
+        Some(entries.map(|entry| Ok(entry?.file_name())).collect())
+    }
+
+    fn open_mode(root: &Path, path: &Path, flags: libc::c_int, mode: u64) -> Option<Result<File>> {
+        let root = CString::new(root.as_os_str().as_bytes()).ok()?;
+        let rel = match path.as_os_str().is_empty() {
+            true => CString::new("."),
+            false => CString::new(path.as_os_str().as_bytes()),
+        }
+        .ok()?;
+        let dir_flags = libc::O_PATH | libc::O_DIRECTORY | libc::O_CLOEXEC;
+        let dir = unsafe { libc::open(root.as_ptr(), dir_flags) };
+        if dir < 0 {
+            return Some(Err(Error::last_os_error()));
+        }
+        let mut how: libc::open_how = unsafe { std::mem::zeroed() };
+        how.flags = (flags | libc::O_CLOEXEC) as u64;
+        how.mode = mode;
+        how.resolve = libc::RESOLVE_BENEATH | libc::RESOLVE_NO_MAGICLINKS;
+        let fd = unsafe {
+            libc::syscall(
+                libc::SYS_openat2,
+                dir,
+                rel.as_ptr(),
+                &how as *const libc::open_how,
+                std::mem::size_of::<libc::open_how>(),
+            )
+        };
+        let error = Error::last_os_error();
+        unsafe { libc::close(dir) };
+        if fd >= 0 {
+            return Some(Ok(unsafe { File::from_raw_fd(fd as libc::c_int) }));
+        }
+        match error.raw_os_error() {
+            Some(libc::ENOSYS) => None,
+            Some(libc::EXDEV) => Some(Err(escape_err(&path.to_string_lossy()))),
+            _ => Some(Err(error)),
+        }
+    }
+}
+
+fn escape_err(path: &str) -> Error {
+    Error::new(
+        ErrorKind::PermissionDenied,
+        format!("The path '{}' escapes from the root directory!", path),
+    )
+}
@@ -0,0 +1,172 @@
+use super::backend::FsMetadata;
+use super::fd::{DFiles, FileDir};
+use super::DirectoryInfo;
+use crate::prelude::compare::natural_cmp;
+use std::cmp::Ordering;
+use std::ops::{Bound, RangeBounds};
+use std::time::{Duration, SystemTime};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum SortBy {
+    /// Natural order ignoring case, `file2` before `file10`
+    Name,
+    Size,
+    Modified,
+    Extension,
+}
+
+/// A sorted and filtered listing of the children of a directory.
+///
+/// The extension, size and age filters only apply to files,
+/// directories are kept unless they are hidden and `hidden(false)` is set.
+#[derive(Debug, Clone)]
+pub struct Listing<'a> {
+    dir: &'a DirectoryInfo,
+    sort: SortBy,
+    dirs_first: bool,
+    reverse: bool,
+    hidden: bool,
+    extensions: Vec<String>,
+    size: (Bound<u64>, Bound<u64>),
+    age: (Bound<Duration>, Bound<Duration>),
+}
+
+impl DirectoryInfo {
+    pub fn listing(&self) -> Listing<'_> {
+        Listing {
+            dir: self,
+            sort: SortBy::Name,
+            dirs_first: false,
+            reverse: false,
+            hidden: true,
+            extensions: Vec::new(),
+            size: (Bound::Unbounded, Bound::Unbounded),
+            age: (Bound::Unbounded, Bound::Unbounded),
+        }
+    }
+}
+
+impl Listing<'_> {
+    pub fn sort_by(mut self, sort: SortBy) -> Self {
+        self.sort = sort;
+        self
+    }
+    pub fn dirs_first(mut self, dirs_first: bool) -> Self {
+        self.dirs_first = dirs_first;
+        self
+    }
+    /// Reverse the order, directories stay first when `dirs_first` is set
+    pub fn reverse(mut self, reverse: bool) -> Self {
+        self.reverse = reverse;
+        self
+    }
+    /// Whether to include entries whose name starts with `.`
+    pub fn hidden(mut self, hidden: bool) -> Self {
+        self.hidden = hidden;
+        self
+    }
+    /// Only keep files with one of `extensions`, compared ignoring ASCII case
+    /// against both the last and the full extension
+    pub fn extensions(mut self, extensions: &[&str]) -> Self {
+        self.extensions = extensions
+            .iter()
+            .map(|ext| match ext.starts_with('.') {
+                true => ext.to_string(),
+                false => format!(".{}", ext),
+            })
+            .collect();
+        self
+    }
+    /// Only keep files whose size in bytes is in `range`
+    pub fn size<R: RangeBounds<u64>>(mut self, range: R) -> Self {
+        self.size = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+    /// Only keep files modified within `range` before now
+    pub fn age<R: RangeBounds<Duration>>(mut self, range: R) -> Self {
+        self.age = (range.start_bound().cloned(), range.end_bound().cloned());
+        self
+    }
+
+    pub fn dir(&self) -> &DirectoryInfo {
+        self.dir
+    }
+    /// The same options applied to another directory
+    pub(crate) fn with_dir<'b>(&self, dir: &'b DirectoryInfo) -> Listing<'b> {
+        Listing {
+            dir,
+            sort: self.sort,
+            dirs_first: self.dirs_first,
+            reverse: self.reverse,
+            hidden: self.hidden,
+            extensions: self.extensions.clone(),
+            size: self.size,
+            age: self.age,
+        }
+    }
+
+    pub fn list(&self) -> Vec<FileDir> {
+        let now = SystemTime::now();
+        let mut entries: Vec<(FileDir, FsMetadata)> = self
+            .dir
+            .children()
+            .into_iter()
+            .filter_map(|entry| {
+                let data = entry.stat().ok()?;
+                match self.keep(&entry, &data, now) {
+                    true => Some((entry, data)),
+                    false => None,
+                }
+            })
+            .collect();
+        entries.sort_by(|(a, a_data), (b, b_data)| {
+            let group = match self.dirs_first {
+                true => b_data.is_dir().cmp(&a_data.is_dir()),
+                false => Ordering::Equal,
+            };
+            group.then_with(|| {
+                let order = self.compare(a, a_data, b, b_data);
+                match self.reverse {
+                    true => order.reverse(),
+                    false => order,
+                }
+            })
+        });
+        entries.into_iter().map(|(entry, _)| entry).collect()
+    }
+
+    fn keep(&self, entry: &FileDir, data: &FsMetadata, now: SystemTime) -> bool {
+        if !self.hidden && entry.is_hide() {
+            return false;
+        }
+        if data.is_dir() {
+            return true;
+        }
+        if !self.extensions.is_empty()
+            && !self.extensions.iter().any(|ext| {
+                ext.eq_ignore_ascii_case(entry.extension())
+                    || ext.eq_ignore_ascii_case(entry.full_extension())
+            })
+        {
+            return false;
+        }
+        let age = now.duration_since(data.modified).unwrap_or_default();
+        self.size.contains(&data.len) && self.age.contains(&age)
+    }
+
+    fn compare(
+        &self,
+        a: &FileDir,
+        a_data: &FsMetadata,
+        b: &FileDir,
+        b_data: &FsMetadata,
+    ) -> Ordering {
+        let by_name = || natural_cmp(a.name(), b.name());
+        match self.sort {
+            SortBy::Name => by_name(),
+            SortBy::Size => a_data.len.cmp(&b_data.len).then_with(by_name),
+            SortBy::Modified => a_data.modified.cmp(&b_data.modified).then_with(by_name),
+            SortBy::Extension => natural_cmp(a.extension(), b.extension()).then_with(by_name),
+        }
+    }
+}
@@ -0,0 +1,427 @@
+use super::backend::{FsBackend, FsKind, FsMetadata};
+use std::collections::BTreeMap;
+use std::io::{Cursor, Error, ErrorKind, Read, Result, Write};
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// An error injected into `MemoryFs` for a path and everything below it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fault {
+    /// ENOSPC, only creating and writing fail
+    NoSpace,
+    /// EACCES, every operation fails
+    PermissionDenied,
+    /// Every operation fails with the given kind
+    Error(ErrorKind),
+}
+
+enum Node {
+    File(Vec<u8>),
+    Directory,
+}
+
+struct Entry {
+    node: Node,
+    modified: SystemTime,
+    readonly: bool,
+}
+
+struct State {
+    entries: BTreeMap<String, Entry>,
+    faults: Vec<(String, Fault)>,
+    now: SystemTime,
+}
+
+/// An in-memory `FsBackend` for tests, with fault injection and a fake clock.
+///
+/// Paths have to be absolute, the same as those produced by `PathBuilder`.
+#[derive(Clone)]
+pub struct MemoryFs {
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for MemoryFs {
+    fn default() -> Self {
+        MemoryFs::new()
+    }
+}
+
+impl MemoryFs {
+    pub fn new() -> MemoryFs {
+        MemoryFs {
+            state: Arc::new(Mutex::new(State {
+                entries: BTreeMap::new(),
+                faults: Vec::new(),
+                now: UNIX_EPOCH,
+            })),
+        }
+    }
+
+    /// Create a file with `contents` and all of its parent directories
+    pub fn add_file<C: AsRef<[u8]>>(&self, path: &str, contents: C) {
+        let mut state = self.lock();
+        if let Some(parent) = parent_of(path) {
+            state.create_dirs(parent);
+        }
+        let now = state.now;
+        state.entries.insert(
+            path.to_string(),
+            Entry {
+                node: Node::File(contents.as_ref().to_vec()),
+                modified: now,
+                readonly: false,
+            },
+        );
+    }
+
+    pub fn add_dir(&self, path: &str) {
+        self.lock().create_dirs(path);
+    }
+
+    /// The contents of a file, ignoring injected faults
+    pub fn contents(&self, path: &str) -> Option<Vec<u8>> {
+        match self.lock().entries.get(path) {
+            Some(Entry {
+                node: Node::File(buf),
+                ..
+            }) => Some(buf.clone()),
+            _ => None,
+        }
+    }
+
+    pub fn set_readonly(&self, path: &str, readonly: bool) {
+        if let Some(entry) = self.lock().entries.get_mut(path) {
+            entry.readonly = readonly;
+        }
+    }
+
+    pub fn inject(&self, path: &str, fault: Fault) {
+        self.lock().faults.push((path.to_string(), fault));
+    }
+
+    pub fn clear_faults(&self) {
+        self.lock().faults.clear();
+    }
+
+    /// The time of the fake clock, used as the modified time of every change
+    pub fn now(&self) -> SystemTime {
+        self.lock().now
+    }
+
+    pub fn set_time(&self, time: SystemTime) {
+        self.lock().now = time;
+    }
+
+    pub fn advance(&self, duration: Duration) {
+        self.lock().now += duration;
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        match self.state.lock() {
+            Ok(state) => state,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+}
+
+impl State {
+    fn check(&self, path: &str, write: bool) -> Result<()> {
+        for (prefix, fault) in &self.faults {
+            if !is_below(path, prefix) {
+                continue;
+            }
+            match fault {
+                Fault::NoSpace if write => {
+                    return Err(Error::new(
+                        ErrorKind::StorageFull,
+                        "No space left on device",
+                    ))
+                }
+                Fault::NoSpace => (),
+                Fault::PermissionDenied => {
+                    return Err(Error::new(ErrorKind::PermissionDenied, "Permission denied"))
+                }
+                Fault::Error(kind) => return Err(Error::new(*kind, "Injected error")),
+            }
+        }
+        Ok(())
+    }
+
+    fn is_dir(&self, path: &str) -> bool {
+        match self.entries.get(path) {
+            Some(entry) => matches!(entry.node, Node::Directory),
+            _ => parent_of(path).is_none(),
+        }
+    }
+
+    fn create_dirs(&mut self, path: &str) {
+        if let Some(parent) = parent_of(path) {
+            self.create_dirs(parent);
+            let now = self.now;
+            self.entries.entry(path.to_string()).or_insert(Entry {
+                node: Node::Directory,
+                modified: now,
+                readonly: false,
+            });
+        }
+    }
+
+    fn check_parent(&self, path: &str) -> Result<()> {
+        match parent_of(path) {
+            Some(parent) if !self.is_dir(parent) => Err(not_found_err(parent)),
+            _ => Ok(()),
+        }
+    }
+
+    fn file(&self, path: &str) -> Result<&Vec<u8>> {
+        match self.entries.get(path) {
+            Some(Entry {
+                node: Node::File(buf),
+                ..
+            }) => Ok(buf),
+            Some(_) => Err(Error::new(ErrorKind::IsADirectory, path.to_string())),
+            _ => Err(not_found_err(path)),
+        }
+    }
+
+    fn children(&self, path: &str) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|key| parent_of(key) == Some(path))
+            .cloned()
+            .collect()
+    }
+
+    fn descendants(&self, path: &str) -> Vec<String> {
+        self.entries
+            .keys()
+            .filter(|key| key.as_str() != path && is_below(key, path))
+            .cloned()
+            .collect()
+    }
+
+    fn write(&mut self, path: &str, buf: &[u8], truncate: bool) -> Result<()> {
+        self.check(path, true)?;
+        let now = self.now;
+        match self.entries.get_mut(path) {
+            Some(entry) if entry.readonly => {
+                Err(Error::new(ErrorKind::PermissionDenied, "Permission denied"))
+            }
+            Some(Entry {
+                node: Node::File(data),
+                modified,
+                ..
+            }) => {
+                if truncate {
+                    data.clear();
+                }
+                data.extend_from_slice(buf);
+                *modified = now;
+                Ok(())
+            }
+            Some(_) => Err(Error::new(ErrorKind::IsADirectory, path.to_string())),
+            _ => Err(not_found_err(path)),
+        }
+    }
+}
+
+impl FsBackend for MemoryFs {
+    fn metadata(&self, path: &Path) -> Result<FsMetadata> {
+        let path = &key(path);
+        let state = self.lock();
+        state.check(path, false)?;
+        match state.entries.get(path) {
+            Some(entry) => Ok(FsMetadata {
+                kind: match entry.node {
+                    Node::File(_) => FsKind::File,
+                    Node::Directory => FsKind::Directory,
+                },
+                len: match &entry.node {
+                    Node::File(buf) => buf.len() as u64,
+                    Node::Directory => 0,
+                },
+                modified: entry.modified,
+                readonly: entry.readonly,
+            }),
+            _ if parent_of(path).is_none() => Ok(FsMetadata {
+                kind: FsKind::Directory,
+                len: 0,
+                modified: UNIX_EPOCH,
+                readonly: false,
+            }),
+            _ => Err(not_found_err(path)),
+        }
+    }
+
+    fn read_dir(&self, path: &Path) -> Result<Vec<PathBuf>> {
+        let path = &key(path);
+        let state = self.lock();
+        state.check(path, false)?;
+        if !state.is_dir(path) {
+            return Err(not_found_err(path));
+        }
+        Ok(state
+            .children(path)
+            .into_iter()
+            .map(PathBuf::from)
+            .collect())
+    }
+
+    fn create_dir_all(&self, path: &Path) -> Result<()> {
+        let path = &key(path);
+        let mut state = self.lock();
+        state.check(path, true)?;
+        match state.entries.get(path) {
+            Some(Entry {
+                node: Node::File(_),
+                ..
+            }) => Err(Error::new(ErrorKind::AlreadyExists, path.to_string())),
+            _ => {
+                state.create_dirs(path);
+                Ok(())
+            }
+        }
+    }
+
+    fn remove_file(&self, path: &Path) -> Result<()> {
+        let path = &key(path);
+        let mut state = self.lock();
+        state.check(path, true)?;
+        state.file(path)?;
+        state.entries.remove(path);
+        Ok(())
+    }
+
+    fn remove_dir_all(&self, path: &Path) -> Result<()> {
+        let path = &key(path);
+        let mut state = self.lock();
+        state.check(path, true)?;
+        if !state.entries.contains_key(path) {
+            return Err(not_found_err(path));
+        }
+        for key in state.descendants(path) {
+            state.check(&key, true)?;
+            state.entries.remove(&key);
+        }
+        state.entries.remove(path);
+        Ok(())
+    }
+
+    fn rename(&self, from: &Path, to: &Path) -> Result<()> {
+        let (from, to) = (&key(from), &key(to));
+        let mut state = self.lock();
+        state.check(from, true)?;
+        state.check(to, true)?;
+        state.check_parent(to)?;
+        let Some(entry) = state.entries.remove(from) else {
+            return Err(not_found_err(from));
+        };
+        for key in state.descendants(from) {
+            if let Some(child) = state.entries.remove(&key) {
+                state
+                    .entries
+                    .insert(format!("{}{}", to, &key[from.len()..]), child);
+            }
+        }
+        state.entries.insert(to.to_string(), entry);
+        Ok(())
+    }
+
+    fn copy(&self, from: &Path, to: &Path) -> Result<u64> {
+        let (from, to) = (&key(from), &key(to));
+        let mut state = self.lock();
+        state.check(from, false)?;
+        state.check(to, true)?;
+        state.check_parent(to)?;
+        let buf = state.file(from)?.clone();
+        let len = buf.len() as u64;
+        let now = state.now;
+        if state.entries.contains_key(to) {
+            state.write(to, &buf, true)?;
+        } else {
+            state.entries.insert(
+                to.to_string(),
+                Entry {
+                    node: Node::File(buf),
+                    modified: now,
+                    readonly: false,
+                },
+            );
+        }
+        Ok(len)
+    }
+
+    fn open_read(&self, path: &Path) -> Result<Box<dyn Read + Send>> {
+        let path = &key(path);
+        let state = self.lock();
+        state.check(path, false)?;
+        Ok(Box::new(Cursor::new(state.file(path)?.clone())))
+    }
+
+    fn open_write(&self, path: &Path, append: bool) -> Result<Box<dyn Write + Send>> {
+        let path = &key(path);
+        let mut state = self.lock();
+        state.check(path, true)?;
+        state.check_parent(path)?;
+        if state.entries.contains_key(path) {
+            state.write(path, &[], !append)?;
+        } else {
+            let now = state.now;
+            state.entries.insert(
+                path.to_string(),
+                Entry {
+                    node: Node::File(Vec::new()),
+                    modified: now,
+                    readonly: false,
+                },
+            );
+        }
+        Ok(Box::new(MemoryWriter {
+            fs: self.clone(),
+            path: path.to_string(),
+        }))
+    }
+}
+
+struct MemoryWriter {
+    fs: MemoryFs,
+    path: String,
+}
+
+impl Write for MemoryWriter {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.fs.lock().write(&self.path, buf, false)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Entries are keyed by the lossy UTF-8 form of their path
+fn key(path: &Path) -> String {
+    path.to_string_lossy().into_owned()
+}
+
+fn parent_of(path: &str) -> Option<&str> {
+    match path.rfind('/') {
+        Some(0) if path.len() > 1 => Some("/"),
+        Some(k) if k + 1 < path.len() => Some(&path[..k]),
+        _ => None,
+    }
+}
+
+fn is_below(path: &str, prefix: &str) -> bool {
+    path == prefix
+        || (path.starts_with(prefix)
+            && (prefix.ends_with('/') || path.as_bytes().get(prefix.len()) == Some(&b'/')))
+}
+
+fn not_found_err(path: &str) -> Error {
+    Error::new(
+        ErrorKind::NotFound,
+        format!("The specified path '{}' cannot be found!", path),
+    )
+}
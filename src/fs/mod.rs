@@ -1,12 +1,28 @@
 pub mod os;
 use self::{dir::DirectoryInfo, file::FileInfo, fd::*};
+pub mod backend;
+pub mod compress;
+pub mod csv;
 pub mod fd;
 pub mod dir;
+pub mod edit;
 pub mod file;
+pub mod follow;
+pub mod ini;
+pub mod jail;
+pub mod listing;
+pub mod memory;
 pub mod stream;
 pub mod pathstr;
 mod other;
 pub mod pbuilder;
+pub mod render;
+pub mod reverse;
+pub mod rotate;
+pub mod search;
+pub mod tar;
+#[cfg(feature = "zip")]
+pub mod zip;
 
 
 
@@ -12,6 +12,9 @@ pub trait PathStr {
     fn correct(&self) -> String;
     fn relate_to_absolute(&self) -> String;
     fn is_root(&self) -> bool;
+    /// Remove `.` and resolve `..` lexically, without touching the file system.
+    /// `..` above the root of an absolute path is dropped.
+    fn normalize(&self) -> String;
 }
 
 impl<P: AsRef<str>> PathStr for P {
@@ -79,6 +82,40 @@ impl<P: AsRef<str>> PathStr for P {
             path.as_str() == "/"
         }
     }
+
+    fn normalize(&self) -> String {
+        let path = self.correct();
+        let bytes = path.as_bytes();
+        let has_drive = bytes.len() > 1 && bytes[0].is_letter() && bytes[1] == b':';
+        let (root, rest) = if cfg!(windows) && has_drive {
+            (
+                format!("{}/", &path[..2]),
+                path[2..].trim_start_matches('/'),
+            )
+        } else if let Some(rest) = path.strip_prefix('/') {
+            ("/".to_string(), rest)
+        } else {
+            (String::new(), path.as_str())
+        };
+        let mut parts: Vec<&str> = Vec::new();
+        for part in rest.split('/') {
+            match part {
+                "" | "." => (),
+                ".." => match parts.last() {
+                    Some(&last) if last != ".." => {
+                        parts.pop();
+                    }
+                    None if !root.is_empty() => (),
+                    _ => parts.push(part),
+                },
+                _ => parts.push(part),
+            }
+        }
+        match (root.is_empty(), parts.is_empty()) {
+            (true, true) => ".".to_string(),
+            _ => format!("{}{}", root, parts.join("/")),
+        }
+    }
 }
 
 pub trait FileString {
@@ -1,7 +1,13 @@
+use crate::env;
 use crate::fs::pathstr::PathStr;
 use crate::prelude::Find;
+use std::ffi::{OsStr, OsString};
 use std::fmt::{Display, Formatter};
-use std::fs::metadata;
+use std::fs::{canonicalize, metadata};
+use std::io::Result;
+use std::path::{Component, Path, PathBuf};
+use std::str::Split;
+use std::sync::{OnceLock, RwLock};
 
 #[derive(Debug)]
 pub enum UriKind {
@@ -9,9 +15,15 @@ pub enum UriKind {
     Relative,
 }
 
+/// An absolute, normalized path.
+///
+/// The path is kept as an `OsString`, so names that are not valid UTF-8 survive
+/// listing, renaming and copying. The `&str` accessors return a lossy copy where
+/// invalid parts are replaced with `U+FFFD`, meant for display.
 #[derive(PartialEq, Clone, Debug)]
 pub struct PathBuilder {
     inner: String,
+    raw: OsString,
 }
 impl Display for PathBuilder {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
@@ -26,21 +38,126 @@ impl<P: AsRef<str>> From<P> for PathBuilder {
             UriKind::Absolute => path,
             _ => value.as_ref().relate_to_absolute().correct(),
         };
-        PathBuilder { inner: path }
+        let path = path.normalize();
+        PathBuilder {
+            raw: OsString::from(&path),
+            inner: path,
+        }
+    }
+}
+
+/// Serialized as the full path, lossy when it is not valid UTF-8
+#[cfg(feature = "serde")]
+impl serde::Serialize for PathBuilder {
+    fn serialize<S: serde::Serializer>(
+        &self,
+        serializer: S,
+    ) -> std::result::Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.full_name())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for PathBuilder {
+    fn deserialize<D: serde::Deserializer<'de>>(
+        deserializer: D,
+    ) -> std::result::Result<Self, D::Error> {
+        Ok(PathBuilder::from(String::deserialize(deserializer)?))
+    }
+}
+
+impl AsRef<Path> for PathBuilder {
+    fn as_ref(&self) -> &Path {
+        self.as_path()
+    }
+}
+
+impl From<PathBuilder> for PathBuf {
+    fn from(value: PathBuilder) -> Self {
+        PathBuf::from(value.raw)
     }
 }
 
 impl PathBuilder {
+    /// Build from a std path without losing names that are not valid UTF-8.
+    /// The names are kept byte for byte, unlike `From<&str>` they are not corrected.
+    pub fn from_path<P: AsRef<Path>>(path: P) -> PathBuilder {
+        let path = path.as_ref();
+        let path = match (path.is_absolute(), std::env::current_dir()) {
+            (false, Ok(dir)) => dir.join(path),
+            _ => path.to_path_buf(),
+        };
+        let mut bytes: Vec<u8> = Vec::new();
+        let mut ends: Vec<usize> = Vec::new();
+        for component in path.components() {
+            match component {
+                Component::Prefix(prefix) => bytes.extend(
+                    prefix
+                        .as_os_str()
+                        .to_string_lossy()
+                        .correct()
+                        .trim_end_matches('/')
+                        .as_bytes(),
+                ),
+                Component::RootDir => bytes.push(b'/'),
+                Component::CurDir => (),
+                Component::ParentDir => {
+                    if let Some(end) = ends.pop() {
+                        bytes.truncate(end);
+                    }
+                }
+                Component::Normal(name) => {
+                    ends.push(bytes.len());
+                    if !bytes.ends_with(b"/") {
+                        bytes.push(b'/');
+                    }
+                    bytes.extend(name.as_encoded_bytes());
+                }
+            }
+        }
+        PathBuilder::from_raw(bytes)
+    }
+    /// Expand `~`, `~user`, `$VAR` and `${VAR:-default}` before building the path
+    pub fn expand<P: AsRef<str>>(path: P) -> Result<PathBuilder> {
+        Ok(PathBuilder::from(env::expand(path.as_ref())?))
+    }
+    pub fn as_path(&self) -> &Path {
+        Path::new(&self.raw)
+    }
+    pub fn as_os_str(&self) -> &OsStr {
+        &self.raw
+    }
+    /// The name of the file or directory, without losing invalid UTF-8
+    pub fn os_name(&self) -> &OsStr {
+        let bytes = self.raw_bytes();
+        match bytes.iter().rposition(|b| *b == b'/') {
+            Some(k) if k + 1 < bytes.len() => os_str(&bytes[k + 1..]),
+            _ => OsStr::new(""),
+        }
+    }
     pub unsafe fn from_uncheck<P: AsRef<str>>(path: P) -> PathBuilder {
         PathBuilder {
             inner: path.as_ref().to_owned(),
+            raw: OsString::from(path.as_ref()),
         }
     }
     pub fn borrow(&self) -> &PathBuilder {
         self
     }
     pub fn is_exist(&self) -> bool {
-        metadata(self.full_name()).is_ok()
+        metadata(self.as_path()).is_ok()
+    }
+    /// Resolve symlinks through the file system, the path has to exist
+    pub fn canonicalize(&self) -> Result<PathBuilder> {
+        Ok(PathBuilder::from_path(canonicalize(self.as_path())?))
+    }
+    /// Compare the canonical paths, falling back to the lexical paths
+    /// when either of them cannot be resolved
+    pub fn canonical_eq(&self, other: &PathBuilder) -> bool {
+        match (self.canonicalize(), other.canonicalize()) {
+            (Ok(path), Ok(other)) => path == other,
+            _ => self == other,
+        }
     }
 
     pub unsafe fn mut_borrow(&mut self) -> &mut PathBuilder {
@@ -50,18 +167,62 @@ impl PathBuilder {
         self.inner.clone()
     }
     pub fn replace(&mut self, from: &str, to: &str) {
-        self.inner = self.inner.replace(from, to);
+        let bytes = self.raw_bytes();
+        let from = from.as_bytes();
+        if from.is_empty() {
+            return;
+        }
+        let mut buf = Vec::with_capacity(bytes.len());
+        let mut pos = 0;
+        while pos < bytes.len() {
+            if bytes[pos..].starts_with(from) {
+                buf.extend_from_slice(to.as_bytes());
+                pos += from.len();
+            } else {
+                buf.push(bytes[pos]);
+                pos += 1;
+            }
+        }
+        self.set_raw(buf);
     }
+    /// The full path, lossy when it is not valid UTF-8
     pub fn full_name(&self) -> &str {
         self.inner.as_str()
     }
+    /// The name without its full extension, the same as `stem`
     pub fn ext_name(&self) -> &str {
+        self.stem()
+    }
+    /// The name without its full extension, `archive` for `archive.tar.gz`
+    pub fn stem(&self) -> &str {
         let name = self.name();
-        match name.as_bytes().find_last(&b'.') {
-            Some(k) if k != 0 => &name[..k],
-            _ => name,
+        match full_extension_pos(name.as_bytes()) {
+            Some(k) => &name[..k],
+            None => name,
         }
     }
+    /// The known compound extension the name ends with, otherwise the last one,
+    /// so `.tar.gz` for `archive.tar.gz` but `.txt` for `v1.2.txt`
+    pub fn full_extension(&self) -> &str {
+        let name = self.name();
+        match full_extension_pos(name.as_bytes()) {
+            Some(k) => &name[k..],
+            None => "",
+        }
+    }
+    /// Every suffix of the name, `[".tar", ".gz"]` for `archive.tar.gz`
+    pub fn extensions(&self) -> Vec<&str> {
+        let name = self.name();
+        let mut starts: Vec<usize> = name
+            .bytes()
+            .enumerate()
+            .skip(1)
+            .filter(|(_, b)| *b == b'.')
+            .map(|(k, _)| k)
+            .collect();
+        starts.push(name.len());
+        starts.windows(2).map(|k| &name[k[0]..k[1]]).collect()
+    }
     pub fn name(&self) -> &str {
         match self.last_pos(b'/') {
             Some(k) if k != 0 => &self.inner.as_str()[k + 1..],
@@ -69,22 +230,28 @@ impl PathBuilder {
         }
     }
     pub fn rename<P: AsRef<str>>(&mut self, name: P) {
-        let pos = self.last_pos(b'/').expect("rename");
-        let extension = self.extension().to_string();
-        unsafe {
-            self.inner.as_mut_vec().set_len(pos + 1);
-            self.inner
-                .push_str(format!("{}{}", name.as_ref(), extension).as_str())
-        }
+        let bytes = self.raw_bytes();
+        let pos = bytes.iter().rposition(|b| *b == b'/').expect("rename");
+        let extension = match full_extension_pos(&bytes[pos + 1..]) {
+            Some(k) => &bytes[pos + 1 + k..],
+            None => &[],
+        };
+        let mut buf = bytes[..pos + 1].to_vec();
+        buf.extend_from_slice(name.as_ref().as_bytes());
+        buf.extend_from_slice(extension);
+        self.set_raw(buf);
     }
     pub fn push(&mut self, ch: char) {
-        self.inner.push(ch)
+        self.push_str(ch.encode_utf8(&mut [0; 4]))
     }
     pub fn push_byte(&mut self, byte: u8) {
-        unsafe { self.inner.as_mut_vec().push(byte) }
+        let mut buf = self.raw_bytes().to_vec();
+        buf.push(byte);
+        self.set_raw(buf);
     }
     pub fn push_str(&mut self, string: &str) {
-        self.inner.push_str(string)
+        self.raw.push(string);
+        self.inner = self.raw.to_string_lossy().into_owned();
     }
     pub fn parent(&self) -> &str {
         if self.inner.is_root() {
@@ -97,41 +264,48 @@ impl PathBuilder {
         }
     }
     pub fn set_parent(&mut self, parent: &str) {
-        self.inner = format!("{}/{}", parent, self.ext_name()).correct();
+        let mut buf = format!("{}/", parent).correct().into_bytes();
+        if !buf.ends_with(b"/") {
+            buf.push(b'/');
+        }
+        let name = self.os_name().as_encoded_bytes();
+        match name.iter().rposition(|b| *b == b'.') {
+            Some(k) if k != 0 => buf.extend_from_slice(&name[..k]),
+            _ => buf.extend_from_slice(name),
+        }
+        self.set_raw(buf);
     }
     pub fn is_hide(&self) -> bool {
         self.ext_name().starts_with(".")
     }
     pub fn set_hide(&mut self, hide: bool) {
-        let mut name = self.ext_name().to_string();
+        if hide == self.is_hide() {
+            return;
+        }
+        let mut buf = self.raw_bytes().to_vec();
+        let pos = buf.iter().rposition(|b| *b == b'/').map_or(0, |k| k + 1);
         if hide {
-            if !self.is_hide() {
-                unsafe {
-                    name.as_mut_vec().insert(0, b'.');
-                }
-                self.rename(name)
-            }
+            buf.insert(pos, b'.');
         } else {
-            if self.is_hide() {
-                unsafe {
-                    name.as_mut_vec().remove(0);
-                }
-                self.rename(name.as_str())
-            }
+            buf.remove(pos);
         }
+        self.set_raw(buf);
     }
 
+    /// Replace the full extension, so `archive.tar.gz` becomes `archive.zip`
     pub fn set_extension(&mut self, extension: &str) {
-        match self.last_pos(b'.') {
-            Some(k) => unsafe { self.inner.as_mut_vec().set_len(k) },
-            _ => (),
+        let mut buf = self.raw_bytes().to_vec();
+        let start = buf.iter().rposition(|b| *b == b'/').map_or(0, |k| k + 1);
+        if let Some(k) = full_extension_pos(&buf[start..]) {
+            buf.truncate(start + k);
         }
         if !extension.starts_with(".") {
-            self.inner.push('.')
+            buf.push(b'.')
         }
-
-        self.inner.push_str(extension);
+        buf.extend_from_slice(extension.as_bytes());
+        self.set_raw(buf);
     }
+    /// The last suffix of the name, `.gz` for `archive.tar.gz`
     pub fn extension(&self) -> &str {
         let ext_name = self.name();
         match ext_name.as_bytes().find_last(&b'.') {
@@ -142,4 +316,199 @@ impl PathBuilder {
     fn last_pos(&self, byte: u8) -> Option<usize> {
         self.inner.as_bytes().find_last(&byte)
     }
+    fn raw_bytes(&self) -> &[u8] {
+        self.raw.as_encoded_bytes()
+    }
+    fn from_raw(bytes: Vec<u8>) -> PathBuilder {
+        let raw = os_string(bytes);
+        PathBuilder {
+            inner: raw.to_string_lossy().into_owned(),
+            raw,
+        }
+    }
+    fn set_raw(&mut self, bytes: Vec<u8>) {
+        *self = PathBuilder::from_raw(bytes);
+    }
+    fn raw_components(&self) -> impl Iterator<Item = &[u8]> {
+        self.raw_bytes()
+            .split(|b| *b == b'/')
+            .filter(|part| !part.is_empty())
+    }
+    fn raw_parent(&self) -> Option<PathBuilder> {
+        if self.inner.is_root() {
+            return None;
+        }
+        let bytes = self.raw_bytes();
+        match bytes.iter().rposition(|b| *b == b'/') {
+            Some(0) => Some(PathBuilder::from_raw(b"/".to_vec())),
+            Some(k) => Some(PathBuilder::from_raw(bytes[..k].to_vec())),
+            None => None,
+        }
+    }
+
+    /// The names between the separators, including the drive on Windows
+    pub fn components(&self) -> Components<'_> {
+        Components {
+            inner: self.inner.split('/'),
+        }
+    }
+    /// The path itself followed by each of its parents up to the root
+    pub fn ancestors(&self) -> Ancestors<'_> {
+        Ancestors {
+            next: Some(self.full_name()),
+        }
+    }
+    /// Append a relative path, an absolute `path` replaces this one
+    pub fn join<P: AsRef<Path>>(&self, path: P) -> PathBuilder {
+        PathBuilder::from_path(self.as_path().join(path))
+    }
+    /// Compare by component, so `/foo/bar` starts with `/foo` but not with `/fo`
+    pub fn starts_with(&self, base: &PathBuilder) -> bool {
+        let mut components = self.raw_components();
+        base.raw_components()
+            .all(|part| components.next() == Some(part))
+    }
+    pub fn ends_with(&self, suffix: &str) -> bool {
+        let suffix = suffix.correct();
+        let suffix: Vec<&[u8]> = suffix
+            .as_bytes()
+            .split(|b| *b == b'/')
+            .filter(|s| !s.is_empty())
+            .collect();
+        self.raw_components().collect::<Vec<_>>().ends_with(&suffix)
+    }
+    /// The rest of the path after `base`, empty when they are the same
+    pub fn strip_prefix(&self, base: &PathBuilder) -> Option<&str> {
+        if !self.starts_with(base) {
+            return None;
+        }
+        Some(self.inner[base.inner.len()..].trim_start_matches('/'))
+    }
+    /// The deepest directory containing both paths, `None` for different drives
+    pub fn common_ancestor(&self, other: &PathBuilder) -> Option<PathBuilder> {
+        let mut path = self.clone();
+        while !other.starts_with(&path) {
+            path = path.raw_parent()?;
+        }
+        Some(path)
+    }
+    /// The path that leads from `base` to this one, with `..` where needed
+    pub fn relative_to(&self, base: &PathBuilder) -> Option<String> {
+        let common = self.common_ancestor(base)?;
+        let depth = common.components().count();
+        let mut parts: Vec<&str> = vec![".."; base.components().count() - depth];
+        parts.extend(self.components().skip(depth));
+        match parts.is_empty() {
+            true => Some(".".to_string()),
+            false => Some(parts.join("/")),
+        }
+    }
+}
+
+pub struct Components<'a> {
+    inner: Split<'a, char>,
+}
+
+impl<'a> Iterator for Components<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.by_ref().find(|part| !part.is_empty())
+    }
+}
+
+pub struct Ancestors<'a> {
+    next: Option<&'a str>,
+}
+
+impl<'a> Iterator for Ancestors<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let path = self.next?;
+        self.next = match path.rfind('/') {
+            _ if path.is_root() => None,
+            Some(0) => Some("/"),
+            Some(k) => Some(&path[..k]),
+            None => None,
+        };
+        Some(path)
+    }
+}
+
+fn compound_list() -> &'static RwLock<Vec<String>> {
+    static COMPOUND: OnceLock<RwLock<Vec<String>>> = OnceLock::new();
+    COMPOUND.get_or_init(|| {
+        let defaults = [
+            ".tar.gz",
+            ".tar.bz2",
+            ".tar.xz",
+            ".tar.zst",
+            ".tar.lz4",
+            ".tar.lzma",
+            ".tar.Z",
+        ];
+        RwLock::new(defaults.iter().map(|ext| ext.to_string()).collect())
+    })
+}
+
+/// The extensions treated as one by `full_extension`, `stem`, `set_extension` and `rename`
+pub fn compound_extensions() -> Vec<String> {
+    match compound_list().read() {
+        Ok(list) => list.clone(),
+        Err(poisoned) => poisoned.into_inner().clone(),
+    }
+}
+
+/// Replace the list of compound extensions, matched ignoring ASCII case
+pub fn set_compound_extensions<S: AsRef<str>>(extensions: &[S]) {
+    let mut list: Vec<String> = extensions
+        .iter()
+        .map(|ext| match ext.as_ref().starts_with('.') {
+            true => ext.as_ref().to_string(),
+            false => format!(".{}", ext.as_ref()),
+        })
+        .collect();
+    list.sort_by_key(|ext| std::cmp::Reverse(ext.len()));
+    match compound_list().write() {
+        Ok(mut guard) => *guard = list,
+        Err(poisoned) => *poisoned.into_inner() = list,
+    }
+}
+
+/// Where the full extension starts in a file name
+fn full_extension_pos(name: &[u8]) -> Option<usize> {
+    let list = match compound_list().read() {
+        Ok(list) => list,
+        Err(poisoned) => poisoned.into_inner(),
+    };
+    for ext in list.iter().map(|ext| ext.as_bytes()) {
+        let Some(k) = name.len().checked_sub(ext.len()) else {
+            continue;
+        };
+        if k > 0 && name[k..].eq_ignore_ascii_case(ext) {
+            return Some(k);
+        }
+    }
+    match name.iter().rposition(|b| *b == b'.') {
+        Some(k) if k != 0 => Some(k),
+        _ => None,
+    }
+}
+
+fn os_str(bytes: &[u8]) -> &OsStr {
+    // Only ever split at `/` or `.`, which keeps the encoding valid
+    unsafe { OsStr::from_encoded_bytes_unchecked(bytes) }
+}
+
+#[cfg(unix)]
+fn os_string(bytes: Vec<u8>) -> OsString {
+    use std::os::unix::ffi::OsStringExt;
+    OsString::from_vec(bytes)
+}
+
+#[cfg(not(unix))]
+fn os_string(bytes: Vec<u8>) -> OsString {
+    // Only ever split at ASCII characters, which keeps the encoding valid
+    unsafe { OsString::from_encoded_bytes_unchecked(bytes) }
 }
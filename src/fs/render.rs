@@ -0,0 +1,323 @@
+use super::backend::FsMetadata;
+use super::fd::{DFiles, FileDir};
+use super::listing::Listing;
+use super::DirectoryInfo;
+use crate::color::colorful::Color;
+use crate::color::ColorString;
+use std::fs::{self, Metadata};
+use std::io::{stdout, IsTerminal};
+use std::time::UNIX_EPOCH;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ColorMode {
+    /// Color only when stdout is a terminal
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorMode {
+    fn enabled(self) -> bool {
+        match self {
+            ColorMode::Auto => stdout().is_terminal(),
+            ColorMode::Always => true,
+            ColorMode::Never => false,
+        }
+    }
+}
+
+impl DirectoryInfo {
+    /// `ls -l` style listing of the children, hidden entries included
+    pub fn render_long(&self, color: ColorMode) -> String {
+        self.listing().render_long(color)
+    }
+    /// `tree` style view with hidden entries, `depth` limits how many levels are shown
+    /// below this directory
+    pub fn render_tree(&self, depth: Option<usize>, color: ColorMode) -> String {
+        self.listing().render_tree(depth, color)
+    }
+}
+
+impl Listing<'_> {
+    /// One line per entry with the permissions, size, modified time and name
+    pub fn render_long(&self, color: ColorMode) -> String {
+        let colors = color.enabled().then(LsColors::from_env);
+        let rows: Vec<(String, String, String, String)> = self
+            .list()
+            .into_iter()
+            .filter_map(|entry| {
+                let data = entry.stat().ok()?;
+                let link = fs::symlink_metadata(entry.as_path()).ok();
+                let mut name = paint(&entry, &data, link.as_ref(), colors.as_ref());
+                if link.as_ref().is_some_and(|link| link.is_symlink()) {
+                    if let Ok(target) = fs::read_link(entry.as_path()) {
+                        name = format!("{} -> {}", name, target.to_string_lossy());
+                    }
+                }
+                Some((
+                    permissions(&data, link.as_ref()),
+                    data.len.to_string(),
+                    format_time(&data),
+                    name,
+                ))
+            })
+            .collect();
+        let width = rows.iter().map(|row| row.1.len()).max().unwrap_or(0);
+        let mut buf = String::new();
+        for (mode, size, time, name) in rows {
+            buf.push_str(&format!("{} {:>width$} {} {}\n", mode, size, time, name));
+        }
+        buf
+    }
+
+    /// The directory followed by its children drawn with box-drawing characters
+    pub fn render_tree(&self, depth: Option<usize>, color: ColorMode) -> String {
+        let colors = color.enabled().then(LsColors::from_env);
+        let root = FileDir::from(self.dir().clone());
+        let mut buf = match root.stat() {
+            Ok(data) => paint(&root, &data, None, colors.as_ref()),
+            _ => root.name().to_string(),
+        };
+        buf.push('\n');
+        self.tree(&mut buf, "", depth, colors.as_ref());
+        buf
+    }
+
+    fn tree(
+        &self,
+        buf: &mut String,
+        prefix: &str,
+        depth: Option<usize>,
+        colors: Option<&LsColors>,
+    ) {
+        if depth == Some(0) {
+            return;
+        }
+        let entries = self.list();
+        for (pos, entry) in entries.iter().enumerate() {
+            let last = pos + 1 == entries.len();
+            let Ok(data) = entry.stat() else {
+                continue;
+            };
+            let link = fs::symlink_metadata(entry.as_path()).ok();
+            buf.push_str(prefix);
+            buf.push_str(if last { "└── " } else { "├── " });
+            buf.push_str(&paint(entry, &data, link.as_ref(), colors));
+            buf.push('\n');
+            let is_link = link.as_ref().is_some_and(|link| link.is_symlink());
+            if data.is_dir() && !is_link {
+                let dir = entry.to_dir();
+                let prefix = format!("{}{}", prefix, if last { "    " } else { "│   " });
+                self.with_dir(&dir)
+                    .tree(buf, &prefix, depth.map(|depth| depth - 1), colors);
+            }
+        }
+    }
+}
+
+/// The styles of `LS_COLORS`, falling back to the defaults of GNU ls
+struct LsColors {
+    dir: Style,
+    link: Style,
+    exec: Style,
+    file: Style,
+    extensions: Vec<(String, Style)>,
+}
+
+#[derive(Clone, Copy, Default)]
+struct Style {
+    fg: Option<Color>,
+    bg: Option<Color>,
+}
+
+impl LsColors {
+    fn from_env() -> LsColors {
+        let mut colors = LsColors {
+            dir: Style::parse("01;34"),
+            link: Style::parse("01;36"),
+            exec: Style::parse("01;32"),
+            file: Style::default(),
+            extensions: Vec::new(),
+        };
+        let Ok(value) = std::env::var("LS_COLORS") else {
+            return colors;
+        };
+        for item in value.split(':') {
+            let Some((key, codes)) = item.split_once('=') else {
+                continue;
+            };
+            let style = Style::parse(codes);
+            match key {
+                "di" => colors.dir = style,
+                "ln" => colors.link = style,
+                "ex" => colors.exec = style,
+                "fi" => colors.file = style,
+                _ => {
+                    if let Some(ext) = key.strip_prefix('*') {
+                        colors.extensions.push((ext.to_ascii_lowercase(), style));
+                    }
+                }
+            }
+        }
+        colors
+    }
+
+    fn style(&self, name: &str, data: &FsMetadata, link: Option<&Metadata>) -> Style {
+        if link.is_some_and(|link| link.is_symlink()) {
+            return self.link;
+        }
+        if data.is_dir() {
+            return self.dir;
+        }
+        if link.is_some_and(is_executable) {
+            return self.exec;
+        }
+        let name = name.to_ascii_lowercase();
+        self.extensions
+            .iter()
+            .filter(|(ext, _)| name.ends_with(ext.as_str()))
+            .max_by_key(|(ext, _)| ext.len())
+            .map_or(self.file, |(_, style)| *style)
+    }
+}
+
+impl Style {
+    /// Parse SGR codes like `01;34` or `38;5;208`, bold turns the basic colors bright
+    fn parse(codes: &str) -> Style {
+        let mut style = Style::default();
+        let mut bold = false;
+        let codes: Vec<usize> = codes
+            .split(';')
+            .filter_map(|code| code.parse().ok())
+            .collect();
+        let mut pos = 0;
+        while pos < codes.len() {
+            match codes[pos] {
+                1 => bold = true,
+                code @ 30..=37 => style.fg = Some(Color::from(code - 30)),
+                code @ 90..=97 => style.fg = Some(Color::from(code - 90 + 8)),
+                code @ 40..=47 => style.bg = Some(Color::from(code - 40)),
+                code @ 100..=107 => style.bg = Some(Color::from(code - 100 + 8)),
+                code @ (38 | 48) if codes.get(pos + 1) == Some(&5) => {
+                    if let Some(&n) = codes.get(pos + 2).filter(|n| **n < 256) {
+                        match code {
+                            38 => style.fg = Some(Color::from(n)),
+                            _ => style.bg = Some(Color::from(n)),
+                        }
+                    }
+                    pos += 2;
+                }
+                _ => (),
+            }
+            pos += 1;
+        }
+        if let Some(fg) = style.fg.filter(|fg| bold && (*fg as usize) < 8) {
+            style.fg = Some(Color::from(fg as usize + 8));
+        }
+        style
+    }
+}
+
+fn paint(
+    entry: &FileDir,
+    data: &FsMetadata,
+    link: Option<&Metadata>,
+    colors: Option<&LsColors>,
+) -> String {
+    let name = entry.name();
+    let Some(colors) = colors else {
+        return name.to_string();
+    };
+    match colors.style(name, data, link) {
+        Style {
+            fg: Some(fg),
+            bg: Some(bg),
+        } => name.set_color(fg, bg),
+        Style {
+            fg: Some(fg),
+            bg: None,
+        } => name.fg(fg),
+        Style {
+            fg: None,
+            bg: Some(bg),
+        } => name.bg(bg),
+        _ => name.to_string(),
+    }
+}
+
+#[cfg(unix)]
+fn is_executable(data: &Metadata) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    data.is_file() && data.permissions().mode() & 0o111 != 0
+}
+#[cfg(not(unix))]
+fn is_executable(_: &Metadata) -> bool {
+    false
+}
+
+#[cfg(unix)]
+fn permissions(data: &FsMetadata, link: Option<&Metadata>) -> String {
+    use std::os::unix::fs::PermissionsExt;
+    let Some(link) = link else {
+        return fallback_permissions(data);
+    };
+    let kind = match link.file_type() {
+        t if t.is_symlink() => 'l',
+        t if t.is_dir() => 'd',
+        t if t.is_file() => '-',
+        _ => '?',
+    };
+    let mode = link.permissions().mode();
+    let mut buf = kind.to_string();
+    for (k, ch) in "rwxrwxrwx".chars().enumerate() {
+        buf.push(if mode & (0o400 >> k) != 0 { ch } else { '-' });
+    }
+    buf
+}
+#[cfg(not(unix))]
+fn permissions(data: &FsMetadata, _: Option<&Metadata>) -> String {
+    fallback_permissions(data)
+}
+
+/// `drwxr-xr-x` style permissions from what every backend knows
+fn fallback_permissions(data: &FsMetadata) -> String {
+    let kind = if data.is_dir() { 'd' } else { '-' };
+    match (data.readonly, data.is_dir()) {
+        (true, true) => format!("{}r-xr-xr-x", kind),
+        (true, false) => format!("{}r--r--r--", kind),
+        (false, true) => format!("{}rwxr-xr-x", kind),
+        (false, false) => format!("{}rw-r--r--", kind),
+    }
+}
+
+/// `YYYY-MM-DD HH:MM` in UTC
+fn format_time(data: &FsMetadata) -> String {
+    let secs = match data.modified.duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs() as i64,
+        _ => 0,
+    };
+    let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+    let rest = secs.rem_euclid(86_400);
+    format!(
+        "{:04}-{:02}-{:02} {:02}:{:02}",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60
+    )
+}
+
+/// Days since 1970-01-01 to a (year, month, day) in the proleptic gregorian calendar
+pub(crate) fn civil_from_days(days: i64) -> (i64, u8, u8) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let doe = z.rem_euclid(146_097);
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u8;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u8;
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
@@ -0,0 +1,123 @@
+use super::backend::os_only_err;
+use super::{DFiles, FileInfo};
+use std::fs::File;
+use std::io::{Error, ErrorKind, Read, Result, Seek, SeekFrom};
+
+const BLOCK_SIZE: u64 = 64 * 1024;
+
+/// The lines of a file from the last to the first, read in blocks backwards from the end.
+///
+/// Lines end in `\n`, `\r\n` or a lone `\r` like `Lines`, and a line break at the
+/// end of the file does not start an empty last line. A line is only decoded once
+/// it is complete, so UTF-8 characters split between blocks are joined first.
+pub struct ReverseLines {
+    file: File,
+    /// The position in the file before the bytes in `buf`
+    pos: u64,
+    buf: Vec<u8>,
+    started: bool,
+    done: bool,
+}
+
+impl FileInfo {
+    /// The lines of the file from the last to the first, only for files of the operating system
+    pub fn reverse_lines(&self) -> Result<ReverseLines> {
+        if !self.backend().is_os() {
+            return Err(os_only_err("Reading lines backwards"));
+        }
+        let mut file = File::open(self.as_path())?;
+        let pos = file.seek(SeekFrom::End(0))?;
+        Ok(ReverseLines {
+            file,
+            pos,
+            buf: Vec::new(),
+            started: false,
+            done: false,
+        })
+    }
+    /// The last `n` lines of the file in order
+    pub fn last_lines(&self, n: usize) -> Result<Vec<String>> {
+        let mut lines = self.reverse_lines()?.take(n).collect::<Result<Vec<_>>>()?;
+        lines.reverse();
+        Ok(lines)
+    }
+}
+
+impl ReverseLines {
+    /// Read the block before `buf`, returns false at the start of the file
+    fn read_block(&mut self) -> Result<bool> {
+        if self.pos == 0 {
+            return Ok(false);
+        }
+        let size = self.pos.min(BLOCK_SIZE);
+        self.pos -= size;
+        self.file.seek(SeekFrom::Start(self.pos))?;
+        let mut block = vec![0; size as usize];
+        self.file.read_exact(&mut block)?;
+        block.append(&mut self.buf);
+        self.buf = block;
+        Ok(true)
+    }
+
+    /// The position of the line break before the last line in `buf`, and its length
+    fn line_break(&self) -> Option<(usize, usize)> {
+        let i = self.buf.iter().rposition(|b| *b == b'\n' || *b == b'\r')?;
+        match self.buf[i] {
+            b'\n' if i > 0 && self.buf[i - 1] == b'\r' => Some((i - 1, 2)),
+            _ => Some((i, 1)),
+        }
+    }
+}
+
+impl Iterator for ReverseLines {
+    type Item = Result<String>;
+
+    fn next(&mut self) -> Option<Result<String>> {
+        if self.done {
+            return None;
+        }
+        if !self.started {
+            self.started = true;
+            // Drop the line break that ends the file
+            loop {
+                match self.read_block() {
+                    Ok(true) if self.buf.len() < 2 && self.pos > 0 => continue,
+                    Ok(_) => break,
+                    Err(e) => return Some(Err(e)),
+                }
+            }
+            if self.buf.ends_with(b"\r\n") {
+                self.buf.truncate(self.buf.len() - 2);
+            } else if self.buf.ends_with(b"\n") || self.buf.ends_with(b"\r") {
+                self.buf.pop();
+            } else if self.buf.is_empty() {
+                self.done = true;
+                return None;
+            }
+        }
+        loop {
+            // A `\n` at the start of `buf` may end a `\r\n` split between blocks
+            match self.line_break() {
+                Some((i, len)) if i > 0 || self.pos == 0 || self.buf[0] == b'\r' => {
+                    let line = self.buf.split_off(i + len);
+                    self.buf.truncate(i);
+                    return Some(utf8(line));
+                }
+                _ => {}
+            }
+            match self.read_block() {
+                Ok(true) => continue,
+                Ok(false) => {
+                    self.done = true;
+                    return Some(utf8(std::mem::take(&mut self.buf)));
+                }
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+fn utf8(line: Vec<u8>) -> Result<String> {
+    String::from_utf8(line)
+        .map_err(|_| Error::new(ErrorKind::InvalidData, "stream did not contain valid UTF-8"))
+}
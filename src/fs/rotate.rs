@@ -0,0 +1,373 @@
+use super::compress::{Compression, FinishWrite};
+use super::pbuilder::PathBuilder;
+use super::render::civil_from_days;
+use super::stream::{BufferStream, FileWriteStream};
+use super::FileInfo;
+use std::fs::{self, File};
+use std::io::{self, Error, ErrorKind, Result};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Start a new file at the start of each hour or day (UTC)
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RotateEvery {
+    Hour,
+    Day,
+}
+
+/// How rotated files are named.
+///
+/// `Numeric` shifts `app.log.1`, `app.log.2`, ... so that `.1` is always the newest.
+/// `Timestamp` names them after the day or hour they cover for time rotation, as
+/// `app.log.2024-05-01` or `app.log.2024-05-01T13`, and after the time of rotation
+/// otherwise, as `app.log.2024-05-01T13-05-09`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum RotateName {
+    Numeric,
+    Timestamp,
+}
+
+/// A log file that is rotated by size, by time or both.
+///
+/// The writes go through a `FileWriteStream` of the current file and are guarded by a
+/// mutex, so a `RotatingWriter` can be shared between threads (e.g. in an `Arc`) and each
+/// `write` ends up whole in one file. A single write larger than `max_size` is not split.
+pub struct RotatingWriter {
+    path: String,
+    max_size: Option<u64>,
+    every: Option<RotateEvery>,
+    naming: RotateName,
+    keep: Option<usize>,
+    compression: Compression,
+    active: Mutex<Option<Active>>,
+}
+
+struct Active {
+    file: FileInfo,
+    size: u64,
+    period: u64,
+}
+
+impl RotatingWriter {
+    pub fn new<P: AsRef<str>>(path: P) -> RotatingWriter {
+        RotatingWriter {
+            path: PathBuilder::from(path).full_name().to_string(),
+            max_size: None,
+            every: None,
+            naming: RotateName::Numeric,
+            keep: None,
+            compression: Compression::None,
+            active: Mutex::new(None),
+        }
+    }
+    /// Rotate before a write would make the file larger than `max_size` bytes
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+    pub fn every(mut self, every: RotateEvery) -> Self {
+        self.every = Some(every);
+        self
+    }
+    /// `RotateName::Numeric` by default
+    pub fn naming(mut self, naming: RotateName) -> Self {
+        self.naming = naming;
+        self
+    }
+    /// Keep only the `keep` newest rotated files, all of them by default
+    pub fn keep(mut self, keep: usize) -> Self {
+        self.keep = Some(keep);
+        self
+    }
+    /// Compress the rotated files, adding the extension of the format to their names.
+    /// Rotating fails before anything is moved when the format is not enabled.
+    pub fn compress(mut self, compression: Compression) -> Self {
+        self.compression = match compression {
+            Compression::Auto => Compression::None,
+            compression => compression,
+        };
+        self
+    }
+    /// The path of the file being written
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn write<T: BufferStream>(&self, contents: T) -> Result<()> {
+        let buf = contents.write_buf();
+        let buf = buf.as_buf();
+        self.with_file(buf.len() as u64, |f| f.write(buf))
+    }
+    pub fn writeln<T: BufferStream>(&self, contents: T) -> Result<()> {
+        let buf = contents.write_buf();
+        let buf = buf.as_buf();
+        self.with_file(buf.len() as u64 + 1, |f| f.writeln(buf))
+    }
+    /// Write out the buffered contents of the current file
+    pub fn flush(&self) -> Result<()> {
+        match self.lock().as_mut() {
+            Some(a) => a.file.flush(),
+            None => Ok(()),
+        }
+    }
+    /// Close the current file, the next write opens it again
+    pub fn close(&self) -> Result<()> {
+        match self.lock().take() {
+            Some(mut a) => a.file.close(),
+            None => Ok(()),
+        }
+    }
+    /// Rotate now, even when the file is not full
+    pub fn rotate(&self) -> Result<()> {
+        let mut active = self.lock();
+        if let Some(mut a) = active.take() {
+            a.file.close()?;
+        }
+        if Path::new(&self.path).exists() {
+            self.rotate_file()?;
+        }
+        Ok(())
+    }
+
+    /// The writer stays usable after a thread panicked while holding the lock
+    fn lock(&self) -> MutexGuard<'_, Option<Active>> {
+        match self.active.lock() {
+            Ok(active) => active,
+            Err(poisoned) => poisoned.into_inner(),
+        }
+    }
+
+    fn with_file<F: FnOnce(&mut FileInfo) -> Result<()>>(&self, len: u64, f: F) -> Result<()> {
+        let mut active = self.lock();
+        let period = self.period(now());
+        let full = |a: &Active| match self.max_size {
+            Some(max_size) => a.size > 0 && a.size + len > max_size,
+            None => false,
+        };
+        if active.is_none() {
+            *active = Some(self.open()?);
+        }
+        if let Some(a) = active.as_ref() {
+            if a.period != period || full(a) {
+                if let Some(mut a) = active.take() {
+                    a.file.close()?;
+                }
+                self.rotate_file()?;
+                *active = Some(self.open()?);
+            }
+        }
+        let a = active.as_mut().unwrap();
+        f(&mut a.file)?;
+        a.size += len;
+        Ok(())
+    }
+
+    fn open(&self) -> Result<Active> {
+        let mut file = FileInfo::open_smart(&self.path)?;
+        file.start_writing()?;
+        let data = fs::metadata(&self.path)?;
+        // An empty file is still in the current period
+        let period = match data.len() {
+            0 => self.period(now()),
+            _ => self.period(modified(&data)?),
+        };
+        Ok(Active {
+            file,
+            size: data.len(),
+            period,
+        })
+    }
+
+    fn period(&self, secs: u64) -> u64 {
+        match self.every {
+            Some(RotateEvery::Hour) => secs / 3600,
+            Some(RotateEvery::Day) => secs / 86_400,
+            None => 0,
+        }
+    }
+
+    fn rotate_file(&self) -> Result<()> {
+        // Nothing is moved when the rotated file can not be compressed
+        if !self.compression.is_enabled() {
+            return Err(compress_err(self.compression));
+        }
+        if self.keep == Some(0) {
+            return fs::remove_file(&self.path);
+        }
+        let rotated = match self.naming {
+            RotateName::Numeric => {
+                self.shift()?;
+                PathBuf::from(format!("{}.1", self.path))
+            }
+            RotateName::Timestamp => self.stamped()?,
+        };
+        fs::rename(&self.path, &rotated)?;
+        if self.compression != Compression::None {
+            compress(&rotated, self.compression)?;
+        }
+        match self.naming {
+            RotateName::Numeric => Ok(()),
+            RotateName::Timestamp => self.prune(),
+        }
+    }
+
+    /// Rename `name.i` to `name.i+1` from the oldest, dropping the ones past `keep`
+    fn shift(&self) -> Result<()> {
+        let mut last = 0;
+        while self.numbered(last + 1).is_some() {
+            last += 1;
+        }
+        for i in (1..=last).rev() {
+            let Some((path, ext)) = self.numbered(i) else {
+                continue;
+            };
+            match self.keep {
+                Some(keep) if i >= keep => fs::remove_file(path)?,
+                _ => fs::rename(path, format!("{}.{}{}", self.path, i + 1, ext))?,
+            }
+        }
+        Ok(())
+    }
+
+    /// The existing file `name.i`, compressed or not
+    fn numbered(&self, i: usize) -> Option<(PathBuf, &'static str)> {
+        [
+            Compression::None,
+            Compression::Gzip,
+            Compression::Zstd,
+            Compression::Xz,
+        ]
+        .iter()
+        .map(|c| {
+            let path = format!("{}.{}{}", self.path, i, c.extension());
+            (PathBuf::from(path), c.extension())
+        })
+        .find(|(path, _)| path.exists())
+    }
+
+    /// A free name after the period of the file or the current time, with a numeric
+    /// suffix when it is taken
+    fn stamped(&self) -> Result<PathBuf> {
+        let secs = match self.every {
+            Some(_) => modified(&fs::metadata(&self.path)?)? as i64,
+            None => now() as i64,
+        };
+        let (year, month, day) = civil_from_days(secs.div_euclid(86_400));
+        let rest = secs.rem_euclid(86_400);
+        let stamp = match self.every {
+            Some(RotateEvery::Day) => format!("{:04}-{:02}-{:02}", year, month, day),
+            Some(RotateEvery::Hour) => {
+                format!("{:04}-{:02}-{:02}T{:02}", year, month, day, rest / 3600)
+            }
+            None => format!(
+                "{:04}-{:02}-{:02}T{:02}-{:02}-{:02}",
+                year,
+                month,
+                day,
+                rest / 3600,
+                rest % 3600 / 60,
+                rest % 60
+            ),
+        };
+        let ext = self.compression.extension();
+        let taken = |name: &str| {
+            Path::new(name).exists() || Path::new(&format!("{}{}", name, ext)).exists()
+        };
+        let mut name = format!("{}.{}", self.path, stamp);
+        let mut n = 0;
+        while taken(&name) {
+            n += 1;
+            name = format!("{}.{}.{}", self.path, stamp, n);
+        }
+        Ok(PathBuf::from(name))
+    }
+
+    /// Remove the oldest timestamped files past `keep`
+    fn prune(&self) -> Result<()> {
+        let Some(keep) = self.keep else {
+            return Ok(());
+        };
+        let path = Path::new(&self.path);
+        let prefix = match path.file_name() {
+            Some(name) => format!("{}.", name.to_string_lossy()),
+            None => return Ok(()),
+        };
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        let mut rotated = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(key) = self.stamp_key(&name, &prefix) {
+                rotated.push((key, name));
+            }
+        }
+        rotated.sort();
+        let old = rotated.len().saturating_sub(keep);
+        for (_, name) in &rotated[..old] {
+            fs::remove_file(dir.join(name))?;
+        }
+        Ok(())
+    }
+
+    /// The timestamp and numeric suffix of a file named by `stamped`, `None` for other files
+    fn stamp_key(&self, name: &str, prefix: &str) -> Option<(String, u64)> {
+        let rest = name.strip_prefix(prefix)?;
+        let rest = [Compression::Gzip, Compression::Zstd, Compression::Xz]
+            .iter()
+            .find_map(|c| rest.strip_suffix(c.extension()))
+            .unwrap_or(rest);
+        let (stamp, n) = match rest.split_once('.') {
+            Some((_, n)) if n.is_empty() || !n.bytes().all(|b| b.is_ascii_digit()) => return None,
+            Some((stamp, n)) => (stamp, n.parse().ok()?),
+            None => (rest, 0),
+        };
+        let shape = match self.every {
+            Some(RotateEvery::Day) => "0000-00-00",
+            Some(RotateEvery::Hour) => "0000-00-00T00",
+            None => "0000-00-00T00-00-00",
+        };
+        let matches = stamp.len() == shape.len()
+            && stamp.bytes().zip(shape.bytes()).all(|(b, s)| match s {
+                b'0' => b.is_ascii_digit(),
+                _ => b == s,
+            });
+        matches.then(|| (stamp.to_string(), n))
+    }
+}
+
+/// Compress `path` into `path` + the extension of `compression` and remove it
+fn compress(path: &Path, compression: Compression) -> Result<()> {
+    let mut dest = path.as_os_str().to_os_string();
+    dest.push(compression.extension());
+    let mut encoder = compression.encoder(File::create(&dest)?)?;
+    io::copy(&mut File::open(path)?, &mut encoder)?;
+    encoder.finish()?;
+    fs::remove_file(path)
+}
+
+fn modified(data: &fs::Metadata) -> Result<u64> {
+    Ok(match data.modified()?.duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs(),
+        Err(_) => 0,
+    })
+}
+
+fn now() -> u64 {
+    match SystemTime::now().duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs(),
+        Err(_) => 0,
+    }
+}
+
+fn compress_err(compression: Compression) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!(
+            "The rotated files can not be compressed, {:?} support is not enabled!",
+            compression
+        ),
+    )
+}
@@ -0,0 +1,205 @@
+use super::stream::read_line_bytes;
+use super::{DFiles, DirectoryInfo, FileInfo};
+use crate::prelude::compare::CharCompare;
+use std::collections::VecDeque;
+use std::fs;
+use std::io::{BufRead, Error, ErrorKind, Result};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::thread;
+
+/// What to search for, built with `Search::literal` or `Search::regex`
+#[derive(Debug, Clone)]
+pub struct Search {
+    pattern: String,
+    regex: bool,
+    ignore_case: bool,
+    context: usize,
+    threads: usize,
+}
+
+/// A line that matches, `line` and `column` start at 1 and `column` counts characters
+#[derive(Debug, Clone)]
+pub struct SearchMatch {
+    pub file: FileInfo,
+    pub line: usize,
+    pub column: usize,
+    pub text: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+enum Matcher {
+    Literal(Vec<char>),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Search {
+    pub fn literal<P: AsRef<str>>(pattern: P) -> Search {
+        Search {
+            pattern: pattern.as_ref().to_string(),
+            regex: false,
+            ignore_case: false,
+            context: 0,
+            threads: thread::available_parallelism().map_or(1, |n| n.get()),
+        }
+    }
+    #[cfg(feature = "regex")]
+    pub fn regex<P: AsRef<str>>(pattern: P) -> Search {
+        Search {
+            regex: true,
+            ..Search::literal(pattern)
+        }
+    }
+    /// Compare letters ignoring case with `CharCompare::eq_ingore_case`
+    pub fn ignore_case(mut self, ignore_case: bool) -> Self {
+        self.ignore_case = ignore_case;
+        self
+    }
+    /// The number of lines to keep before and after each match
+    pub fn context(mut self, context: usize) -> Self {
+        self.context = context;
+        self
+    }
+    /// The number of files searched at once, the number of CPUs by default
+    pub fn threads(mut self, threads: usize) -> Self {
+        self.threads = threads.max(1);
+        self
+    }
+
+    fn matcher(&self) -> Result<Matcher> {
+        if self.pattern.is_empty() {
+            return Err(Error::new(ErrorKind::InvalidInput, "The pattern is empty!"));
+        }
+        if self.regex {
+            #[cfg(feature = "regex")]
+            return match regex::RegexBuilder::new(&self.pattern)
+                .case_insensitive(self.ignore_case)
+                .build()
+            {
+                Ok(regex) => Ok(Matcher::Regex(regex)),
+                Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+            };
+        }
+        Ok(Matcher::Literal(self.pattern.chars().collect()))
+    }
+}
+
+impl Matcher {
+    /// The character column of the first match in `line`
+    fn find(&self, line: &str, ignore_case: bool) -> Option<usize> {
+        match self {
+            Matcher::Literal(pattern) => {
+                let chars: Vec<char> = line.chars().collect();
+                chars.windows(pattern.len()).position(|window| {
+                    window.iter().zip(pattern).all(|(a, b)| match ignore_case {
+                        true => a.eq_ingore_case(b),
+                        false => a == b,
+                    })
+                })
+            }
+            #[cfg(feature = "regex")]
+            Matcher::Regex(regex) => {
+                let start = regex.find(line)?.start();
+                Some(line[..start].chars().count())
+            }
+        }
+    }
+}
+
+impl FileInfo {
+    /// The lines of the file that match `search`, nothing for a binary file
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchMatch>> {
+        search_file(self, search, &search.matcher()?)
+    }
+}
+
+impl DirectoryInfo {
+    /// The lines that match `search` in every file under the directory, searched in parallel
+    /// and sorted by path and line. Binary files and files that can not be read are skipped,
+    /// symlinked directories are not followed.
+    pub fn search(&self, search: &Search) -> Result<Vec<SearchMatch>> {
+        let matcher = search.matcher()?;
+        let mut files = Vec::new();
+        let mut dirs = vec![self.clone()];
+        while let Some(dir) = dirs.pop() {
+            files.append(&mut dir.files());
+            dirs.extend(
+                dir.directories()
+                    .into_iter()
+                    .filter(|d| !is_symlink(d.as_path())),
+            );
+        }
+        let next = AtomicUsize::new(0);
+        let found = Mutex::new(Vec::new());
+        thread::scope(|scope| {
+            for _ in 0..search.threads.min(files.len()) {
+                scope.spawn(|| {
+                    while let Some(f) = files.get(next.fetch_add(1, Ordering::Relaxed)) {
+                        if let Ok(mut matches) = search_file(f, search, &matcher) {
+                            found.lock().unwrap().append(&mut matches);
+                        }
+                    }
+                });
+            }
+        });
+        let mut found = found.into_inner().unwrap();
+        found.sort_by(|a, b| {
+            a.file
+                .as_path()
+                .cmp(b.file.as_path())
+                .then(a.line.cmp(&b.line))
+        });
+        Ok(found)
+    }
+}
+
+fn is_symlink(path: &Path) -> bool {
+    fs::symlink_metadata(path).is_ok_and(|data| data.file_type().is_symlink())
+}
+
+fn search_file(f: &FileInfo, search: &Search, matcher: &Matcher) -> Result<Vec<SearchMatch>> {
+    let mut reader = f.reader()?;
+    if reader.fill_buf()?.contains(&0) {
+        return Ok(Vec::new());
+    }
+    let mut matches: Vec<SearchMatch> = Vec::new();
+    let mut before = VecDeque::with_capacity(search.context);
+    // The matches that still need lines after them
+    let mut open = 0;
+    let mut buf = Vec::new();
+    let mut number = 0;
+    loop {
+        buf.clear();
+        if read_line_bytes(&mut reader, &mut buf)? == 0 {
+            break;
+        }
+        number += 1;
+        let line = String::from_utf8_lossy(&buf).into_owned();
+        while open < matches.len() && matches[open].after.len() >= search.context {
+            open += 1;
+        }
+        for m in &mut matches[open..] {
+            m.after.push(line.clone());
+        }
+        if let Some(column) = matcher.find(&line, search.ignore_case) {
+            matches.push(SearchMatch {
+                file: f.clone(),
+                line: number,
+                column: column + 1,
+                text: line.clone(),
+                before: before.iter().cloned().collect(),
+                after: Vec::new(),
+            });
+        }
+        if search.context > 0 {
+            if before.len() == search.context {
+                before.pop_front();
+            }
+            before.push_back(line);
+        }
+    }
+    Ok(matches)
+}
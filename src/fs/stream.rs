@@ -1,8 +1,11 @@
 use crate::io::ConResult;
 use crate::io::ConvertBuffer;
-use std::fs::File;
+use crate::io::ConvertError;
 use std::io;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+
+/// The buffered, possibly decompressing, reader behind a read stream
+pub type StreamReader = BufReader<Box<dyn Read + Send>>;
 
 pub trait FileReadStream {
     fn start_reading(&mut self) -> io::Result<()>;
@@ -12,7 +15,24 @@ pub trait FileReadStream {
     fn read_to_string(&mut self) -> io::Result<String>;
     fn read_until<B: ConvertBuffer>(&mut self, byte: u8) -> ConResult<B>;
     fn read_line<B: ConvertBuffer>(&mut self) -> ConResult<B>;
-    fn lines(&self) -> ConResult<Lines<BufReader<File>>>;
+    fn lines(&self) -> ConResult<Lines<StreamReader>>;
+    /// The bytes up to and including `byte`, nothing is stripped.
+    /// Reads one byte at a time unless implemented.
+    fn read_until_raw(&mut self, byte: u8) -> ConResult<Vec<u8>> {
+        let mut buf = Vec::new();
+        let mut next = [0];
+        while buf.last() != Some(&byte) {
+            match self.read_exact(&mut next) {
+                Ok(()) => buf.push(next[0]),
+                Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => break,
+                Err(e) => return Err(e.into()),
+            }
+        }
+        match buf.is_empty() {
+            true => Err(ConvertError::Empty),
+            false => Ok(buf),
+        }
+    }
 }
 
 pub struct Lines<B> {
@@ -28,19 +48,98 @@ impl<B: io::BufRead> Iterator for Lines<B> {
     type Item = io::Result<String>;
 
     fn next(&mut self) -> Option<io::Result<String>> {
-        let mut buf = String::new();
-        match self.reader.read_line(&mut buf) {
+        let mut buf = Vec::new();
+        match read_line_bytes(&mut self.reader, &mut buf) {
             Ok(0) => None,
-            Ok(_n) => {
-                if buf.ends_with('\n') {
-                    buf.pop();
-                    if buf.ends_with('\r') {
-                        buf.pop();
-                    }
+            Ok(_n) => match String::from_utf8(buf) {
+                Ok(line) => Some(Ok(line)),
+                Err(_) => Some(Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "stream did not contain valid UTF-8",
+                ))),
+            },
+            Err(e) => Some(Err(e)),
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LineEnding {
+    Lf,
+    CrLf,
+    Cr,
+    /// `CrLf` on Windows and `Lf` elsewhere
+    Native,
+    /// The line ending the file already uses most, `Native` for a new file
+    Preserve,
+}
+
+impl LineEnding {
+    /// `Preserve` is written as `Native`
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+            LineEnding::Cr => "\r",
+            LineEnding::Native | LineEnding::Preserve if cfg!(windows) => "\r\n",
+            LineEnding::Native | LineEnding::Preserve => "\n",
+        }
+    }
+    /// The line ending `buf` has most, `None` when it has no line break
+    pub fn detect(buf: &[u8]) -> Option<LineEnding> {
+        let (mut lf, mut crlf, mut cr) = (0, 0, 0);
+        let mut bytes = buf.iter().peekable();
+        while let Some(byte) = bytes.next() {
+            match byte {
+                b'\n' => lf += 1,
+                b'\r' if bytes.next_if_eq(&&b'\n').is_some() => crlf += 1,
+                b'\r' => cr += 1,
+                _ => {}
+            }
+        }
+        match (lf, crlf, cr) {
+            (0, 0, 0) => None,
+            _ if crlf >= lf && crlf >= cr => Some(LineEnding::CrLf),
+            _ if lf >= cr => Some(LineEnding::Lf),
+            _ => Some(LineEnding::Cr),
+        }
+    }
+}
+
+/// Read a line ending in `\n`, `\r\n` or a lone `\r` into `buf` without the line ending,
+/// returns the bytes read including the line ending
+pub fn read_line_bytes<R: io::BufRead + ?Sized>(
+    reader: &mut R,
+    buf: &mut Vec<u8>,
+) -> io::Result<usize> {
+    let mut read = 0;
+    loop {
+        let available = match reader.fill_buf() {
+            Ok(available) => available,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+        if available.is_empty() {
+            return Ok(read);
+        }
+        match available.iter().position(|b| *b == b'\n' || *b == b'\r') {
+            Some(i) => {
+                let cr = available[i] == b'\r';
+                buf.extend_from_slice(&available[..i]);
+                reader.consume(i + 1);
+                read += i + 1;
+                if cr && reader.fill_buf()?.first() == Some(&b'\n') {
+                    reader.consume(1);
+                    read += 1;
                 }
-                Some(Ok(buf))
+                return Ok(read);
+            }
+            None => {
+                let n = available.len();
+                buf.extend_from_slice(available);
+                reader.consume(n);
+                read += n;
             }
-            Err(e) => Some(Err(e)),
         }
     }
 }
@@ -116,4 +215,52 @@ pub trait FileWriteStream {
     fn write<T: BufferStream>(&mut self, contents: T) -> io::Result<()>;
     fn writeln<T: BufferStream>(&mut self, contents: T) -> io::Result<()>;
     fn overwrite<T: BufferStream>(&mut self, contents: T) -> io::Result<()>;
+    /// Write out the buffered contents
+    fn flush(&mut self) -> io::Result<()>;
+    /// Flush and sync the contents to the disk
+    fn sync_data(&mut self) -> io::Result<()>;
+    /// Flush and sync the contents and the metadata to the disk
+    fn sync_all(&mut self) -> io::Result<()>;
+    /// Flush and end the write stream, returning the errors that dropping it would ignore
+    fn close(&mut self) -> io::Result<()>;
+
+    /// Overwrite the file with `value` as JSON
+    #[cfg(feature = "json")]
+    fn write_json<T: serde::Serialize>(&mut self, value: &T, pretty: bool) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        let buf = match pretty {
+            true => serde_json::to_vec_pretty(value),
+            false => serde_json::to_vec(value),
+        };
+        self.overwrite(buf.map_err(serialize_err)?)
+    }
+
+    /// Overwrite the file with `value` as TOML
+    #[cfg(feature = "toml")]
+    fn write_toml<T: serde::Serialize>(&mut self, value: &T, pretty: bool) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        let buf = match pretty {
+            true => toml::to_string_pretty(value),
+            false => toml::to_string(value),
+        };
+        self.overwrite(buf.map_err(serialize_err)?)
+    }
+
+    /// Overwrite the file with `value` as YAML
+    #[cfg(feature = "yaml")]
+    fn write_yaml<T: serde::Serialize>(&mut self, value: &T) -> io::Result<()>
+    where
+        Self: Sized,
+    {
+        self.overwrite(serde_yaml::to_string(value).map_err(serialize_err)?)
+    }
+}
+
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+fn serialize_err<E: std::fmt::Display>(error: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, error.to_string())
 }
@@ -0,0 +1,806 @@
+use super::backend::OsBackend;
+use super::{DFiles, DirectoryInfo, FileInfo};
+use crate::fs::pbuilder::PathBuilder;
+use std::collections::HashMap;
+use std::fs::{self, read_dir, symlink_metadata, File, Metadata};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result, Write};
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const BLOCK: usize = 512;
+const OCTAL_SIZE_MAX: u64 = 0o77777777777;
+/// The largest pax header or GNU long name read into memory
+const META_MAX: u64 = 1024 * 1024;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    HardLink,
+    Other(u8),
+}
+
+impl EntryKind {
+    fn from_flag(flag: u8) -> EntryKind {
+        match flag {
+            b'0' | b'\0' | b'7' => EntryKind::File,
+            b'1' => EntryKind::HardLink,
+            b'2' => EntryKind::Symlink,
+            b'5' => EntryKind::Directory,
+            _ => EntryKind::Other(flag),
+        }
+    }
+    fn flag(&self) -> u8 {
+        match self {
+            EntryKind::File => b'0',
+            EntryKind::HardLink => b'1',
+            EntryKind::Symlink => b'2',
+            EntryKind::Directory => b'5',
+            EntryKind::Other(flag) => *flag,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TarEntry {
+    path: String,
+    kind: EntryKind,
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    uid: u64,
+    gid: u64,
+    link_name: Option<String>,
+}
+
+impl TarEntry {
+    /// The path stored in the archive, always separated by `/`
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+    pub fn kind(&self) -> EntryKind {
+        self.kind
+    }
+    pub fn mode(&self) -> u32 {
+        self.mode
+    }
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    /// Seconds since the unix epoch
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+    pub fn modified(&self) -> SystemTime {
+        UNIX_EPOCH + Duration::from_secs(self.mtime)
+    }
+    pub fn link_name(&self) -> Option<&str> {
+        self.link_name.as_deref()
+    }
+    pub fn is_file(&self) -> bool {
+        self.kind == EntryKind::File
+    }
+    pub fn is_dir(&self) -> bool {
+        self.kind == EntryKind::Directory
+    }
+}
+
+/// Reads the entries of a tar archive one after another without extracting them.
+///
+/// The data of the current entry can be read with `read_data` or `copy_data`,
+/// otherwise it is skipped when moving to the next entry.
+pub struct TarArchive<R> {
+    reader: R,
+    remaining: u64,
+    padding: u64,
+    done: bool,
+}
+
+impl<R: Read> TarArchive<R> {
+    pub fn new(reader: R) -> TarArchive<R> {
+        TarArchive {
+            reader,
+            remaining: 0,
+            padding: 0,
+            done: false,
+        }
+    }
+
+    pub fn next_entry(&mut self) -> Result<Option<TarEntry>> {
+        self.skip_data()?;
+        if self.done {
+            return Ok(None);
+        }
+        let mut pax = HashMap::new();
+        let mut long_name = None;
+        let mut long_link = None;
+        loop {
+            let mut block = [0; BLOCK];
+            if !read_block(&mut self.reader, &mut block)? || block.iter().all(|b| *b == 0) {
+                self.done = true;
+                return Ok(None);
+            }
+            if checksum(&block) != parse_number(&block[148..156])? {
+                return Err(invalid_err("The tar header checksum does not match!"));
+            }
+            let size = parse_number(&block[124..136])?;
+            match block[156] {
+                b'x' => {
+                    pax.extend(parse_pax(&read_exact_vec(&mut self.reader, size)?)?);
+                    continue;
+                }
+                b'g' => {
+                    read_exact_vec(&mut self.reader, size)?;
+                    continue;
+                }
+                b'L' => {
+                    long_name = Some(c_string(&read_exact_vec(&mut self.reader, size)?));
+                    continue;
+                }
+                b'K' => {
+                    long_link = Some(c_string(&read_exact_vec(&mut self.reader, size)?));
+                    continue;
+                }
+                _ => (),
+            }
+            let mut entry = header_to_entry(&block, size)?;
+            if let Some(name) = long_name {
+                entry.path = name;
+            }
+            if let Some(name) = long_link {
+                entry.link_name = Some(name);
+            }
+            apply_pax(&mut entry, &pax)?;
+            let data = match entry.kind {
+                EntryKind::File | EntryKind::Other(_) => entry.size,
+                _ => 0,
+            };
+            self.remaining = data;
+            self.padding = padding(data);
+            return Ok(Some(entry));
+        }
+    }
+
+    /// Read the data of the current entry into memory
+    pub fn read_data(&mut self) -> Result<Vec<u8>> {
+        let mut buf = Vec::new();
+        self.copy_data(&mut buf)?;
+        Ok(buf)
+    }
+
+    /// Copy the data of the current entry into `writer`, return the number of bytes copied
+    pub fn copy_data<W: Write>(&mut self, writer: &mut W) -> Result<u64> {
+        let copied = std::io::copy(&mut (&mut self.reader).take(self.remaining), writer)?;
+        if copied != self.remaining {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The tar archive is truncated!",
+            ));
+        }
+        self.remaining = 0;
+        Ok(copied)
+    }
+
+    fn skip_data(&mut self) -> Result<()> {
+        let len = self.remaining + self.padding;
+        let skipped = std::io::copy(&mut (&mut self.reader).take(len), &mut std::io::sink())?;
+        if skipped != len {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The tar archive is truncated!",
+            ));
+        }
+        self.remaining = 0;
+        self.padding = 0;
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for TarArchive<R> {
+    type Item = Result<TarEntry>;
+
+    fn next(&mut self) -> Option<Result<TarEntry>> {
+        match self.next_entry() {
+            Ok(entry) => entry.map(Ok),
+            Err(e) => {
+                self.done = true;
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+impl DirectoryInfo {
+    /// Pack the directory into a tar archive at `dest`.
+    /// Entries are stored under the name of the directory, like `tar -cf dest dir` does.
+    pub fn pack_tar(&self, dest: &str) -> Result<FileInfo> {
+        let dest = PathBuilder::from(dest);
+        let mut writer = TarWriter::new(BufWriter::new(File::create(dest.as_path())?));
+        for (path, name, data) in walk(self)? {
+            if data.is_symlink() {
+                let link = fs::read_link(&path)?
+                    .display()
+                    .to_string()
+                    .replace("\\", "/");
+                writer.append(&entry_of(name, EntryKind::Symlink, &data, Some(link)), None)?;
+            } else if data.is_dir() {
+                writer.append(
+                    &entry_of(format!("{}/", name), EntryKind::Directory, &data, None),
+                    None,
+                )?;
+            } else if data.is_file() && path != dest.as_path() {
+                let mut f = File::open(&path)?;
+                writer.append(&entry_of(name, EntryKind::File, &data, None), Some(&mut f))?;
+            }
+        }
+        writer.finish()?;
+        Ok(FileInfo::from_builder(dest, OsBackend::shared()))
+    }
+}
+
+impl FileInfo {
+    /// Iterate over the entries of a tar archive without extracting it
+    pub fn tar_entries(&self) -> Result<TarArchive<BufReader<File>>> {
+        Ok(TarArchive::new(BufReader::new(File::open(self.as_path())?)))
+    }
+
+    /// Extract a tar archive into `dest_dir`, creating it when needed.
+    ///
+    /// Entries that are absolute, contain `..`, pass through a symlink or link
+    /// outside of `dest_dir` are rejected with `ErrorKind::InvalidData`.
+    /// Symlinks are created last, after the files and directories.
+    pub fn unpack_tar(&self, dest_dir: &str) -> Result<DirectoryInfo> {
+        let root = DirectoryInfo::open_smart(dest_dir)?;
+        let mut archive = self.tar_entries()?;
+        let mut dirs = Vec::new();
+        let mut links = Vec::new();
+        while let Some(entry) = archive.next_entry()? {
+            let Some(rel) = sanitize(entry.path())? else {
+                continue;
+            };
+            let target = format!("{}/{}", root.full_name(), rel);
+            check_no_symlink(root.full_name(), &rel)?;
+            match entry.kind() {
+                EntryKind::Directory => {
+                    fs::create_dir_all(&target)?;
+                    dirs.push((target, entry));
+                }
+                EntryKind::File => {
+                    create_parent(&target)?;
+                    remove_link(&target)?;
+                    let mut f = File::create(&target)?;
+                    archive.copy_data(&mut f)?;
+                    set_mode(&target, entry.mode())?;
+                    f.set_modified(entry.modified())?;
+                }
+                EntryKind::Symlink => {
+                    let link = entry.link_name().unwrap_or_default();
+                    links.push((rel, link.to_string()));
+                }
+                EntryKind::HardLink => {
+                    let link = entry.link_name().unwrap_or_default();
+                    let Some(source) = sanitize(link)? else {
+                        return Err(escape_err(link));
+                    };
+                    check_no_symlink(root.full_name(), &source)?;
+                    create_parent(&target)?;
+                    remove_link(&target)?;
+                    fs::hard_link(format!("{}/{}", root.full_name(), source), &target)?;
+                }
+                EntryKind::Other(_) => (),
+            }
+        }
+        create_links(root.full_name(), &links)?;
+        for (path, entry) in dirs.into_iter().rev() {
+            set_mode(&path, entry.mode())?;
+            if let Ok(f) = File::open(&path) {
+                f.set_modified(entry.modified()).unwrap_or_default();
+            }
+        }
+        Ok(root)
+    }
+}
+
+struct TarWriter<W: Write> {
+    inner: W,
+}
+
+impl<W: Write> TarWriter<W> {
+    fn new(inner: W) -> TarWriter<W> {
+        TarWriter { inner }
+    }
+
+    fn append(&mut self, entry: &TarEntry, data: Option<&mut dyn Read>) -> Result<()> {
+        let mut pax = Vec::new();
+        let (prefix, name) = match split_name(&entry.path) {
+            Some(split) => split,
+            None => {
+                pax.push(("path", entry.path.clone()));
+                ("", truncate(&entry.path, 100))
+            }
+        };
+        let link = entry.link_name.as_deref().unwrap_or_default();
+        if link.len() > 100 {
+            pax.push(("linkpath", link.to_string()));
+        }
+        if entry.size > OCTAL_SIZE_MAX {
+            pax.push(("size", entry.size.to_string()));
+        }
+        if !pax.is_empty() {
+            let mut records = Vec::new();
+            for (key, value) in pax {
+                records.extend(pax_record(key, &value));
+            }
+            let pax_name = truncate(&format!("PaxHeaders/{}", name), 100).to_string();
+            let mut header = header(
+                &pax_name,
+                "",
+                b'x',
+                0o644,
+                records.len() as u64,
+                entry.mtime,
+                "",
+            );
+            fill_checksum(&mut header);
+            self.inner.write_all(&header)?;
+            self.write_data(&mut records.as_slice(), records.len() as u64)?;
+        }
+        let mut header = header(
+            name,
+            prefix,
+            entry.kind.flag(),
+            entry.mode,
+            entry.size,
+            entry.mtime,
+            truncate(link, 100),
+        );
+        write_number(&mut header[108..116], entry.uid);
+        write_number(&mut header[116..124], entry.gid);
+        fill_checksum(&mut header);
+        self.inner.write_all(&header)?;
+        match data {
+            Some(reader) => self.write_data(reader, entry.size),
+            _ => Ok(()),
+        }
+    }
+
+    fn write_data(&mut self, reader: &mut dyn Read, size: u64) -> Result<()> {
+        let written = std::io::copy(&mut reader.take(size), &mut self.inner)?;
+        if written != size {
+            return Err(Error::new(
+                ErrorKind::UnexpectedEof,
+                "The file changed while packing it!",
+            ));
+        }
+        self.inner.write_all(&vec![0; padding(size) as usize])
+    }
+
+    fn finish(mut self) -> Result<()> {
+        self.inner.write_all(&[0; BLOCK * 2])?;
+        self.inner.flush()
+    }
+}
+
+fn header(
+    name: &str,
+    prefix: &str,
+    flag: u8,
+    mode: u32,
+    size: u64,
+    mtime: u64,
+    link: &str,
+) -> [u8; BLOCK] {
+    let mut block = [0; BLOCK];
+    block[..name.len()].copy_from_slice(name.as_bytes());
+    write_number(&mut block[100..108], mode as u64);
+    write_number(&mut block[108..116], 0);
+    write_number(&mut block[116..124], 0);
+    write_number(&mut block[124..136], size);
+    write_number(&mut block[136..148], mtime);
+    block[156] = flag;
+    block[157..157 + link.len()].copy_from_slice(link.as_bytes());
+    block[257..263].copy_from_slice(b"ustar\0");
+    block[263..265].copy_from_slice(b"00");
+    block[345..345 + prefix.len()].copy_from_slice(prefix.as_bytes());
+    block
+}
+
+fn header_to_entry(block: &[u8; BLOCK], size: u64) -> Result<TarEntry> {
+    let name = c_string(&block[..100]);
+    let path = if &block[257..262] == b"ustar" && block[345] != 0 {
+        format!("{}/{}", c_string(&block[345..500]), name)
+    } else {
+        name
+    };
+    let link = c_string(&block[157..257]);
+    Ok(TarEntry {
+        path,
+        kind: EntryKind::from_flag(block[156]),
+        mode: parse_number(&block[100..108])? as u32,
+        size,
+        mtime: parse_number(&block[136..148])?,
+        uid: parse_number(&block[108..116])?,
+        gid: parse_number(&block[116..124])?,
+        link_name: if link.is_empty() { None } else { Some(link) },
+    })
+}
+
+fn entry_of(path: String, kind: EntryKind, data: &Metadata, link_name: Option<String>) -> TarEntry {
+    let mtime = data
+        .modified()
+        .ok()
+        .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+        .map(|time| time.as_secs())
+        .unwrap_or_default();
+    let (mode, uid, gid) = owner_mode(data);
+    TarEntry {
+        path,
+        kind,
+        mode,
+        size: if kind == EntryKind::File {
+            data.len()
+        } else {
+            0
+        },
+        mtime,
+        uid,
+        gid,
+        link_name,
+    }
+}
+
+/// List `dir` and everything below it without following symlinks, as
+/// `(full path, path inside the archive, metadata)` with parents first
+pub(crate) fn walk(dir: &DirectoryInfo) -> Result<Vec<(PathBuf, String, Metadata)>> {
+    let mut entries = Vec::new();
+    let mut queue = vec![(dir.as_path().to_path_buf(), dir.name().to_string())];
+    while !queue.is_empty() {
+        let (path, name) = queue.remove(0);
+        let data = symlink_metadata(&path)?;
+        if data.is_dir() {
+            let mut children = Vec::new();
+            for child in read_dir(&path)? {
+                let child_name = child?.file_name();
+                children.push((
+                    path.join(&child_name),
+                    format!("{}/{}", name, child_name.to_string_lossy()),
+                ));
+            }
+            children.sort();
+            for (pos, child) in children.into_iter().enumerate() {
+                queue.insert(pos, child);
+            }
+        }
+        entries.push((path, name, data));
+    }
+    Ok(entries)
+}
+
+#[cfg(unix)]
+pub(crate) fn owner_mode(data: &Metadata) -> (u32, u64, u64) {
+    use std::os::unix::fs::MetadataExt;
+    (data.mode() & 0o7777, data.uid() as u64, data.gid() as u64)
+}
+#[cfg(not(unix))]
+pub(crate) fn owner_mode(data: &Metadata) -> (u32, u64, u64) {
+    let mode = match (data.is_dir(), data.permissions().readonly()) {
+        (true, _) => 0o755,
+        (false, true) => 0o444,
+        (false, false) => 0o644,
+    };
+    (mode, 0, 0)
+}
+
+#[cfg(unix)]
+pub(crate) fn set_mode(path: &str, mode: u32) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode & 0o777))
+}
+#[cfg(not(unix))]
+pub(crate) fn set_mode(path: &str, mode: u32) -> Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(mode & 0o222 == 0);
+    fs::set_permissions(path, permissions)
+}
+
+#[cfg(unix)]
+pub(crate) fn symlink(link: &str, path: &str) -> Result<()> {
+    std::os::unix::fs::symlink(link, path)
+}
+#[cfg(windows)]
+pub(crate) fn symlink(link: &str, path: &str) -> Result<()> {
+    std::os::windows::fs::symlink_file(link, path)
+}
+#[cfg(not(any(unix, windows)))]
+pub(crate) fn symlink(_link: &str, _path: &str) -> Result<()> {
+    Ok(())
+}
+
+/// Split a path into the ustar `prefix` and `name` fields
+fn split_name(path: &str) -> Option<(&str, &str)> {
+    if path.len() <= 100 {
+        return Some(("", path));
+    }
+    for (pos, byte) in path.bytes().enumerate() {
+        if byte == b'/' && pos <= 155 && path.len() - pos - 1 <= 100 && pos + 1 < path.len() {
+            return Some((&path[..pos], &path[pos + 1..]));
+        }
+    }
+    None
+}
+
+fn truncate(value: &str, len: usize) -> &str {
+    let mut end = value.len().min(len);
+    while !value.is_char_boundary(end) {
+        end -= 1;
+    }
+    &value[..end]
+}
+
+fn pax_record(key: &str, value: &str) -> Vec<u8> {
+    let rest = key.len() + value.len() + 3;
+    let mut len = rest + rest.to_string().len();
+    if len.to_string().len() + rest != len {
+        len += 1;
+    }
+    format!("{} {}={}\n", len, key, value).into_bytes()
+}
+
+fn parse_pax(buf: &[u8]) -> Result<HashMap<String, String>> {
+    let mut records = HashMap::new();
+    let mut rest = buf;
+    while !rest.is_empty() && rest[0] != 0 {
+        let Some(space) = rest.iter().position(|b| *b == b' ') else {
+            break;
+        };
+        let len: usize = match std::str::from_utf8(&rest[..space])
+            .ok()
+            .and_then(|n| n.parse().ok())
+        {
+            Some(len) if len >= space + 2 && len <= rest.len() => len,
+            _ => return Err(invalid_err("The pax header is malformed!")),
+        };
+        let record = String::from_utf8_lossy(&rest[space + 1..len - 1]).to_string();
+        if let Some((key, value)) = record.split_once('=') {
+            records.insert(key.to_string(), value.to_string());
+        }
+        rest = &rest[len..];
+    }
+    Ok(records)
+}
+
+fn apply_pax(entry: &mut TarEntry, pax: &HashMap<String, String>) -> Result<()> {
+    let number = |key: &str| -> Result<Option<u64>> {
+        match pax.get(key) {
+            Some(value) => match value.split('.').next().unwrap_or_default().parse() {
+                Ok(n) => Ok(Some(n)),
+                _ => Err(invalid_err("The pax header is malformed!")),
+            },
+            _ => Ok(None),
+        }
+    };
+    if let Some(path) = pax.get("path") {
+        entry.path = path.clone();
+    }
+    if let Some(link) = pax.get("linkpath") {
+        entry.link_name = Some(link.clone());
+    }
+    if let Some(size) = number("size")? {
+        entry.size = size;
+    }
+    if let Some(mtime) = number("mtime")? {
+        entry.mtime = mtime;
+    }
+    if let Some(uid) = number("uid")? {
+        entry.uid = uid;
+    }
+    if let Some(gid) = number("gid")? {
+        entry.gid = gid;
+    }
+    Ok(())
+}
+
+fn write_number(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let octal = format!("{:0width$o}", value, width = width);
+    if octal.len() <= width {
+        field[..width].copy_from_slice(octal.as_bytes());
+        field[width] = 0;
+    } else {
+        // base-256 for values that do not fit in octal
+        let bytes = value.to_be_bytes();
+        field.iter_mut().for_each(|b| *b = 0);
+        let start = field.len() - bytes.len();
+        field[start..].copy_from_slice(&bytes);
+        field[0] |= 0x80;
+    }
+}
+
+fn parse_number(field: &[u8]) -> Result<u64> {
+    if !field.is_empty() && field[0] & 0x80 != 0 {
+        let mut value: u64 = (field[0] & 0x7f) as u64;
+        for byte in &field[1..] {
+            value = (value << 8) | *byte as u64;
+        }
+        return Ok(value);
+    }
+    let text = String::from_utf8_lossy(field);
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, 8)
+        .map_err(|_| invalid_err("The tar header contains an invalid number!"))
+}
+
+fn checksum(block: &[u8; BLOCK]) -> u64 {
+    block
+        .iter()
+        .enumerate()
+        .map(|(pos, b)| {
+            if (148..156).contains(&pos) {
+                b' ' as u64
+            } else {
+                *b as u64
+            }
+        })
+        .sum()
+}
+
+fn fill_checksum(block: &mut [u8; BLOCK]) {
+    let sum = checksum(block);
+    block[148..156].copy_from_slice(format!("{:06o}\0 ", sum).as_bytes());
+}
+
+fn padding(size: u64) -> u64 {
+    (BLOCK as u64 - size % BLOCK as u64) % BLOCK as u64
+}
+
+fn c_string(buf: &[u8]) -> String {
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).to_string()
+}
+
+fn read_block<R: Read>(reader: &mut R, block: &mut [u8; BLOCK]) -> Result<bool> {
+    let mut read = 0;
+    while read < BLOCK {
+        match reader.read(&mut block[read..])? {
+            0 if read == 0 => return Ok(false),
+            0 => {
+                return Err(Error::new(
+                    ErrorKind::UnexpectedEof,
+                    "The tar archive is truncated!",
+                ))
+            }
+            n => read += n,
+        }
+    }
+    Ok(true)
+}
+
+fn read_exact_vec<R: Read>(reader: &mut R, size: u64) -> Result<Vec<u8>> {
+    if size > META_MAX {
+        return Err(invalid_err("The tar header is too large!"));
+    }
+    let mut buf = vec![0; size as usize];
+    reader.read_exact(&mut buf)?;
+    let mut pad = vec![0; padding(size) as usize];
+    reader.read_exact(&mut pad)?;
+    Ok(buf)
+}
+
+/// Turn an archive path into a relative path below the extraction root,
+/// `None` means the entry is the root itself
+pub(crate) fn sanitize(path: &str) -> Result<Option<String>> {
+    let path = path.replace("\\", "/");
+    let bytes = path.as_bytes();
+    if path.starts_with('/') || (bytes.len() > 1 && bytes[1] == b':') {
+        return Err(escape_err(&path));
+    }
+    let mut parts = Vec::new();
+    for part in path.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => return Err(escape_err(&path)),
+            _ => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        Ok(None)
+    } else {
+        Ok(Some(parts.join("/")))
+    }
+}
+
+/// Check that a symlink at `rel` pointing to `link` resolves below `root`.
+///
+/// Every `..` has to leave a real directory that is already on disk, so no symlink
+/// extracted before or after can redirect it. Call it once the other entries are written.
+pub(crate) fn check_link(root: &str, rel: &str, link: &str) -> Result<()> {
+    let link = link.replace("\\", "/");
+    if link.starts_with('/') || (link.len() > 1 && link.as_bytes()[1] == b':') {
+        return Err(escape_err(rel));
+    }
+    let mut parts: Vec<_> = rel.split('/').collect();
+    parts.pop();
+    for part in link.split('/') {
+        match part {
+            "" | "." => (),
+            ".." => {
+                if parts.is_empty() || !is_real_dir(root, &parts) {
+                    return Err(escape_err(rel));
+                }
+                parts.pop();
+            }
+            _ => parts.push(part),
+        }
+    }
+    Ok(())
+}
+
+/// Whether `root/parts` and each directory on the way are directories and not symlinks
+fn is_real_dir(root: &str, parts: &[&str]) -> bool {
+    let mut path = root.to_string();
+    parts.iter().all(|part| {
+        path.push('/');
+        path.push_str(part);
+        matches!(symlink_metadata(&path), Ok(data) if data.is_dir())
+    })
+}
+
+/// Create the symlinks deferred until the rest of an archive is extracted
+pub(crate) fn create_links(root: &str, links: &[(String, String)]) -> Result<()> {
+    for (rel, link) in links {
+        check_no_symlink(root, rel)?;
+        check_link(root, rel, link)?;
+        let target = format!("{}/{}", root, rel);
+        create_parent(&target)?;
+        remove_link(&target)?;
+        symlink(link, &target)?;
+    }
+    Ok(())
+}
+
+pub(crate) fn check_no_symlink(root: &str, rel: &str) -> Result<()> {
+    let mut path = root.to_string();
+    let mut parts: Vec<_> = rel.split('/').collect();
+    parts.pop();
+    for part in parts {
+        path.push('/');
+        path.push_str(part);
+        match symlink_metadata(&path) {
+            Ok(data) if data.is_symlink() => return Err(escape_err(rel)),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn create_parent(path: &str) -> Result<()> {
+    match path.rfind('/') {
+        Some(k) if k != 0 => fs::create_dir_all(&path[..k]),
+        _ => Ok(()),
+    }
+}
+
+pub(crate) fn remove_link(path: &str) -> Result<()> {
+    match symlink_metadata(path) {
+        Ok(data) if data.is_symlink() => fs::remove_file(path),
+        _ => Ok(()),
+    }
+}
+
+fn invalid_err(msg: &str) -> Error {
+    Error::new(ErrorKind::InvalidData, msg)
+}
+
+pub(crate) fn escape_err(path: &str) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "The entry '{}' points outside of the destination directory!",
+            path
+        ),
+    )
+}
@@ -0,0 +1,245 @@
+use super::backend::OsBackend;
+use super::render::civil_from_days;
+use super::tar::{
+    check_no_symlink, create_links, create_parent, owner_mode, remove_link, sanitize, set_mode,
+    walk,
+};
+use super::{DFiles, DirectoryInfo, FileInfo};
+use crate::fs::pbuilder::PathBuilder;
+use ::zip::write::SimpleFileOptions;
+use ::zip::{CompressionMethod, DateTime, ZipArchive, ZipWriter};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Error, ErrorKind, Read, Result};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const ZIP64_LIMIT: u64 = 0xFFFF_FFFF;
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum ZipMethod {
+    Stored,
+    Deflated,
+    Other,
+}
+
+impl ZipMethod {
+    /// `Other` only describes entries read from an archive and can not be written
+    fn to_compression(self) -> Result<CompressionMethod> {
+        match self {
+            ZipMethod::Stored => Ok(CompressionMethod::Stored),
+            ZipMethod::Deflated => Ok(CompressionMethod::Deflated),
+            ZipMethod::Other => Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The zip method is not supported for packing!",
+            )),
+        }
+    }
+}
+
+impl From<CompressionMethod> for ZipMethod {
+    fn from(value: CompressionMethod) -> Self {
+        match value {
+            CompressionMethod::Stored => ZipMethod::Stored,
+            CompressionMethod::Deflated => ZipMethod::Deflated,
+            _ => ZipMethod::Other,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ZipEntry {
+    name: String,
+    size: u64,
+    compressed_size: u64,
+    crc32: u32,
+    method: ZipMethod,
+    is_dir: bool,
+    is_symlink: bool,
+    mode: Option<u32>,
+}
+
+impl ZipEntry {
+    /// The name stored in the archive, always separated by `/`
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+    pub fn size(&self) -> u64 {
+        self.size
+    }
+    pub fn compressed_size(&self) -> u64 {
+        self.compressed_size
+    }
+    pub fn crc32(&self) -> u32 {
+        self.crc32
+    }
+    pub fn method(&self) -> ZipMethod {
+        self.method
+    }
+    pub fn is_dir(&self) -> bool {
+        self.is_dir
+    }
+    pub fn is_symlink(&self) -> bool {
+        self.is_symlink
+    }
+    pub fn mode(&self) -> Option<u32> {
+        self.mode
+    }
+}
+
+impl DirectoryInfo {
+    /// Pack the directory into a zip archive at `dest`.
+    /// Entries are stored under the name of the directory and files over 4 GB use ZIP64.
+    pub fn pack_zip(&self, dest: &str, method: ZipMethod) -> Result<FileInfo> {
+        let method = method.to_compression()?;
+        let dest = PathBuilder::from(dest);
+        let mut writer = ZipWriter::new(BufWriter::new(File::create(dest.as_path())?));
+        for (path, name, data) in walk(self)? {
+            let (mode, _, _) = owner_mode(&data);
+            let options = SimpleFileOptions::default()
+                .compression_method(method)
+                .unix_permissions(mode)
+                .last_modified_time(to_dos_time(data.modified().unwrap_or(UNIX_EPOCH)));
+            if data.is_symlink() {
+                let link = fs::read_link(&path)?
+                    .display()
+                    .to_string()
+                    .replace("\\", "/");
+                writer.add_symlink(name, link, options)?;
+            } else if data.is_dir() {
+                writer.add_directory(format!("{}/", name), options)?;
+            } else if data.is_file() && path != dest.as_path() {
+                writer.start_file(name, options.large_file(data.len() >= ZIP64_LIMIT))?;
+                std::io::copy(&mut File::open(&path)?, &mut writer)?;
+            }
+        }
+        writer.finish()?;
+        Ok(FileInfo::from_builder(dest, OsBackend::shared()))
+    }
+}
+
+impl FileInfo {
+    /// List the entries of a zip archive without extracting it
+    pub fn zip_entries(&self) -> Result<Vec<ZipEntry>> {
+        let mut archive = self.zip_archive()?;
+        let mut entries = Vec::new();
+        for i in 0..archive.len() {
+            let f = archive.by_index_raw(i)?;
+            entries.push(ZipEntry {
+                name: f.name().to_string(),
+                size: f.size(),
+                compressed_size: f.compressed_size(),
+                crc32: f.crc32(),
+                method: ZipMethod::from(f.compression()),
+                is_dir: f.is_dir(),
+                is_symlink: f.is_symlink(),
+                mode: f.unix_mode(),
+            });
+        }
+        Ok(entries)
+    }
+
+    /// Extract the single entry `name` of a zip archive into the file `dest`
+    pub fn unzip_entry(&self, name: &str, dest: &str) -> Result<FileInfo> {
+        let mut archive = self.zip_archive()?;
+        let mut entry = archive.by_name(name)?;
+        if entry.is_dir() {
+            return Err(Error::new(
+                ErrorKind::InvalidInput,
+                "The zip entry is a directory!",
+            ));
+        }
+        let f = FileInfo::open(dest);
+        create_parent(f.full_name())?;
+        let mut out = File::create(f.full_name())?;
+        std::io::copy(&mut entry, &mut out)?;
+        if let Some(time) = entry.last_modified() {
+            out.set_modified(from_dos_time(time))?;
+        }
+        Ok(f)
+    }
+
+    /// Extract a zip archive into `dest_dir`, creating it when needed.
+    ///
+    /// Entries that are absolute, contain `..`, pass through a symlink or link
+    /// outside of `dest_dir` are rejected with `ErrorKind::InvalidData`.
+    /// Symlinks are created last, after the files and directories.
+    pub fn unpack_zip(&self, dest_dir: &str) -> Result<DirectoryInfo> {
+        let root = DirectoryInfo::open_smart(dest_dir)?;
+        let mut archive = self.zip_archive()?;
+        let mut links = Vec::new();
+        for i in 0..archive.len() {
+            let mut entry = archive.by_index(i)?;
+            let Some(rel) = sanitize(entry.name())? else {
+                continue;
+            };
+            let target = format!("{}/{}", root.full_name(), rel);
+            check_no_symlink(root.full_name(), &rel)?;
+            if entry.is_dir() {
+                fs::create_dir_all(&target)?;
+                continue;
+            }
+            if entry.is_symlink() {
+                let mut link = String::new();
+                entry.read_to_string(&mut link)?;
+                links.push((rel, link));
+                continue;
+            }
+            create_parent(&target)?;
+            remove_link(&target)?;
+            let mut f = File::create(&target)?;
+            std::io::copy(&mut entry, &mut f)?;
+            if let Some(mode) = entry.unix_mode() {
+                set_mode(&target, mode)?;
+            }
+            if let Some(time) = entry.last_modified() {
+                f.set_modified(from_dos_time(time))?;
+            }
+        }
+        create_links(root.full_name(), &links)?;
+        Ok(root)
+    }
+
+    fn zip_archive(&self) -> Result<ZipArchive<BufReader<File>>> {
+        let f = BufReader::new(File::open(self.as_path())?);
+        Ok(ZipArchive::new(f)?)
+    }
+}
+
+fn to_dos_time(time: SystemTime) -> DateTime {
+    let secs = match time.duration_since(UNIX_EPOCH) {
+        Ok(time) => time.as_secs(),
+        _ => 0,
+    };
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    let rest = secs % 86_400;
+    match u16::try_from(year) {
+        Ok(year) => DateTime::from_date_and_time(
+            year,
+            month,
+            day,
+            (rest / 3600) as u8,
+            (rest % 3600 / 60) as u8,
+            (rest % 60) as u8,
+        )
+        .unwrap_or_default(),
+        _ => DateTime::default(),
+    }
+}
+
+fn from_dos_time(time: DateTime) -> SystemTime {
+    let days = days_from_civil(time.year() as i64, time.month(), time.day());
+    let secs = days * 86_400
+        + time.hour() as i64 * 3600
+        + time.minute() as i64 * 60
+        + time.second() as i64;
+    UNIX_EPOCH + Duration::from_secs(secs.max(0) as u64)
+}
+
+fn days_from_civil(year: i64, month: u8, day: u8) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let yoe = year.rem_euclid(400);
+    let month = month as i64;
+    let doy = (153 * if month > 2 { month - 3 } else { month + 9 } + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146_097 + doe - 719_468
+}
@@ -8,6 +8,12 @@ pub enum ConvertError {
     Empty,
     Invalid,
     IoError(Error),
+    /// A structured document failed to parse, `line` and `column` start at 1
+    Parse {
+        line: usize,
+        column: usize,
+        message: String,
+    },
 }
 impl From<Error> for ConvertError {
     fn from(value: Error) -> Self {
@@ -21,6 +27,14 @@ impl Into<Error> for ConvertError {
             ConvertError::IoError(e) => e,
             ConvertError::Invalid => Error::new(ErrorKind::Other, "ConvertError::Invalid"),
             ConvertError::Empty => Error::new(ErrorKind::Other, "ConvertError::Empty"),
+            ConvertError::Parse {
+                line,
+                column,
+                message,
+            } => Error::new(
+                ErrorKind::InvalidData,
+                format!("line {}, column {}: {}", line, column, message),
+            ),
         }
     }
 }
@@ -0,0 +1,370 @@
+use crate::fs::compress::FinishWrite;
+use std::io::{Error, ErrorKind, Read, Result, Write};
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Encoding {
+    Utf8,
+    Utf16Le,
+    Utf16Be,
+    Utf32Le,
+    Utf32Be,
+    /// ISO-8859-1, every byte is the code point of the same value
+    Latin1,
+    Windows1252,
+}
+
+/// How a file is decoded when read and encoded when written
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub(crate) struct TextOptions {
+    pub encoding: Option<Encoding>,
+    pub lossy: bool,
+    pub bom: bool,
+}
+
+/// The characters of Windows-1252 from 0x80 to 0x9F, the five undefined bytes map to C1 controls
+const WINDOWS_1252: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+impl Encoding {
+    /// The encoding and length of the byte order mark at the start of `buf`
+    pub fn from_bom(buf: &[u8]) -> Option<(Encoding, usize)> {
+        match buf {
+            [0xEF, 0xBB, 0xBF, ..] => Some((Encoding::Utf8, 3)),
+            [0xFF, 0xFE, 0, 0, ..] => Some((Encoding::Utf32Le, 4)),
+            [0, 0, 0xFE, 0xFF, ..] => Some((Encoding::Utf32Be, 4)),
+            [0xFF, 0xFE, ..] => Some((Encoding::Utf16Le, 2)),
+            [0xFE, 0xFF, ..] => Some((Encoding::Utf16Be, 2)),
+            _ => None,
+        }
+    }
+    /// Guess the encoding of `buf` from its byte order mark, then from its zero bytes,
+    /// falling back to Windows-1252 when it is not valid UTF-8
+    pub fn detect(buf: &[u8]) -> Encoding {
+        if let Some((encoding, _)) = Encoding::from_bom(buf) {
+            return encoding;
+        }
+        let sample = &buf[..buf.len().min(4096)];
+        let zeros = |start: usize| sample.iter().skip(start).step_by(2).filter(|b| **b == 0);
+        let (even, odd) = (zeros(0).count(), zeros(1).count());
+        if sample.len() >= 2 && odd > sample.len() / 4 && even == 0 {
+            Encoding::Utf16Le
+        } else if sample.len() >= 2 && even > sample.len() / 4 && odd == 0 {
+            Encoding::Utf16Be
+        } else if valid_utf8_prefix(sample) {
+            Encoding::Utf8
+        } else {
+            Encoding::Windows1252
+        }
+    }
+    pub fn bom(self) -> &'static [u8] {
+        match self {
+            Encoding::Utf8 => &[0xEF, 0xBB, 0xBF],
+            Encoding::Utf16Le => &[0xFF, 0xFE],
+            Encoding::Utf16Be => &[0xFE, 0xFF],
+            Encoding::Utf32Le => &[0xFF, 0xFE, 0, 0],
+            Encoding::Utf32Be => &[0, 0, 0xFE, 0xFF],
+            Encoding::Latin1 | Encoding::Windows1252 => &[],
+        }
+    }
+    /// Decode all of `buf`, a byte order mark is not skipped
+    pub fn decode(self, buf: &[u8], lossy: bool) -> Result<String> {
+        let mut text = String::new();
+        self.decode_to(buf, true, lossy, &mut text)
+            .map_err(|pos| invalid_err(self, pos))?;
+        Ok(text)
+    }
+    /// Encode `text`, unmappable characters become `?` when `lossy` is set
+    pub fn encode(self, text: &str, lossy: bool) -> Result<Vec<u8>> {
+        let mut buf = Vec::with_capacity(text.len());
+        for ch in text.chars() {
+            match self {
+                Encoding::Utf8 => buf.extend_from_slice(ch.encode_utf8(&mut [0; 4]).as_bytes()),
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    for unit in ch.encode_utf16(&mut [0; 2]) {
+                        buf.extend_from_slice(&match self {
+                            Encoding::Utf16Le => unit.to_le_bytes(),
+                            _ => unit.to_be_bytes(),
+                        });
+                    }
+                }
+                Encoding::Utf32Le => buf.extend_from_slice(&(ch as u32).to_le_bytes()),
+                Encoding::Utf32Be => buf.extend_from_slice(&(ch as u32).to_be_bytes()),
+                Encoding::Latin1 | Encoding::Windows1252 => match self.single_byte(ch) {
+                    Some(byte) => buf.push(byte),
+                    None if lossy => buf.push(b'?'),
+                    None => return Err(unmappable_err(self, ch)),
+                },
+            }
+        }
+        Ok(buf)
+    }
+
+    fn single_byte(self, ch: char) -> Option<u8> {
+        let code = ch as u32;
+        if self == Encoding::Windows1252 {
+            if let Some(i) = WINDOWS_1252.iter().position(|c| *c == ch) {
+                return Some(0x80 + i as u8);
+            }
+            if (0x80..0xA0).contains(&code) {
+                return None;
+            }
+        }
+        u8::try_from(code).ok()
+    }
+
+    /// Decode the complete characters of `buf` into `text` and return the bytes used,
+    /// an incomplete character at the end is left unless `eof` is set.
+    /// The error is the position of the first invalid byte.
+    fn decode_to(
+        self,
+        buf: &[u8],
+        eof: bool,
+        lossy: bool,
+        text: &mut String,
+    ) -> std::result::Result<usize, usize> {
+        let mut pos = 0;
+        while pos < buf.len() {
+            let rest = &buf[pos..];
+            let (ch, len) = match self {
+                Encoding::Latin1 => (Some(rest[0] as char), 1),
+                Encoding::Windows1252 => match rest[0] {
+                    byte @ 0x80..=0x9F => (Some(WINDOWS_1252[byte as usize - 0x80]), 1),
+                    byte => (Some(byte as char), 1),
+                },
+                Encoding::Utf8 => match std::str::from_utf8(rest) {
+                    Ok(valid) => {
+                        text.push_str(valid);
+                        return Ok(buf.len());
+                    }
+                    Err(e) if e.valid_up_to() > 0 => {
+                        let valid = &rest[..e.valid_up_to()];
+                        text.push_str(unsafe { std::str::from_utf8_unchecked(valid) });
+                        pos += valid.len();
+                        continue;
+                    }
+                    Err(e) => match e.error_len() {
+                        Some(len) => (None, len),
+                        None if eof => (None, rest.len()),
+                        None => break,
+                    },
+                },
+                Encoding::Utf16Le | Encoding::Utf16Be => {
+                    let unit = |i: usize| -> Option<u16> {
+                        let bytes = [*rest.get(i)?, *rest.get(i + 1)?];
+                        Some(match self {
+                            Encoding::Utf16Le => u16::from_le_bytes(bytes),
+                            _ => u16::from_be_bytes(bytes),
+                        })
+                    };
+                    match (unit(0), unit(2)) {
+                        (None, _) | (Some(0xD800..=0xDBFF), None) if !eof => break,
+                        (None, _) => (None, rest.len()),
+                        (Some(high @ 0xD800..=0xDBFF), Some(low @ 0xDC00..=0xDFFF)) => {
+                            let code =
+                                0x10000 + ((high as u32 - 0xD800) << 10) + (low as u32 - 0xDC00);
+                            (char::from_u32(code), 4)
+                        }
+                        (Some(unit), _) => (char::from_u32(unit as u32), 2),
+                    }
+                }
+                Encoding::Utf32Le | Encoding::Utf32Be => match rest.get(..4) {
+                    Some(bytes) => {
+                        let bytes = [bytes[0], bytes[1], bytes[2], bytes[3]];
+                        let code = match self {
+                            Encoding::Utf32Le => u32::from_le_bytes(bytes),
+                            _ => u32::from_be_bytes(bytes),
+                        };
+                        (char::from_u32(code), 4)
+                    }
+                    None if eof => (None, rest.len()),
+                    None => break,
+                },
+            };
+            match ch {
+                Some(ch) => text.push(ch),
+                None if lossy => text.push(char::REPLACEMENT_CHARACTER),
+                None => return Err(pos),
+            }
+            pos += len;
+        }
+        Ok(pos)
+    }
+}
+
+/// A reader that decodes `encoding` into UTF-8, a byte order mark at the start
+/// overrides `encoding` and is skipped
+pub struct DecodeReader<R: Read> {
+    inner: R,
+    encoding: Encoding,
+    lossy: bool,
+    started: bool,
+    eof: bool,
+    /// The total bytes decoded, for the position of errors
+    offset: usize,
+    input: Vec<u8>,
+    output: Vec<u8>,
+    pos: usize,
+}
+
+impl<R: Read> DecodeReader<R> {
+    pub fn new(inner: R, encoding: Encoding, lossy: bool) -> DecodeReader<R> {
+        DecodeReader {
+            inner,
+            encoding,
+            lossy,
+            started: false,
+            eof: false,
+            offset: 0,
+            input: Vec::new(),
+            output: Vec::new(),
+            pos: 0,
+        }
+    }
+    pub fn encoding(&self) -> Encoding {
+        self.encoding
+    }
+
+    fn fill(&mut self) -> Result<()> {
+        let mut chunk = [0; 8192];
+        while self.pos == self.output.len() && !(self.eof && self.input.is_empty()) {
+            if !self.eof {
+                match self.inner.read(&mut chunk)? {
+                    0 => self.eof = true,
+                    n => self.input.extend_from_slice(&chunk[..n]),
+                }
+            }
+            if !self.started {
+                if self.input.len() < 4 && !self.eof {
+                    continue;
+                }
+                self.started = true;
+                if let Some((encoding, len)) = Encoding::from_bom(&self.input) {
+                    self.encoding = encoding;
+                    self.input.drain(..len);
+                    self.offset = len;
+                }
+            }
+            let mut text = String::new();
+            let used = match self
+                .encoding
+                .decode_to(&self.input, self.eof, self.lossy, &mut text)
+            {
+                Ok(used) => used,
+                Err(pos) => return Err(invalid_err(self.encoding, self.offset + pos)),
+            };
+            self.input.drain(..used);
+            self.offset += used;
+            self.output = text.into_bytes();
+            self.pos = 0;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Read for DecodeReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        self.fill()?;
+        let n = buf.len().min(self.output.len() - self.pos);
+        buf[..n].copy_from_slice(&self.output[self.pos..self.pos + n]);
+        self.pos += n;
+        Ok(n)
+    }
+}
+
+/// A writer that takes UTF-8 and writes it in `encoding`
+pub struct EncodeWriter<W: Write> {
+    inner: W,
+    encoding: Encoding,
+    lossy: bool,
+    pending: Vec<u8>,
+}
+
+impl<W: Write> EncodeWriter<W> {
+    /// Write the byte order mark of `encoding` first when `bom` is set
+    pub fn new(
+        mut inner: W,
+        encoding: Encoding,
+        lossy: bool,
+        bom: bool,
+    ) -> Result<EncodeWriter<W>> {
+        if bom {
+            inner.write_all(encoding.bom())?;
+        }
+        Ok(EncodeWriter {
+            inner,
+            encoding,
+            lossy,
+            pending: Vec::new(),
+        })
+    }
+}
+
+impl<W: Write> Write for EncodeWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.pending.extend_from_slice(buf);
+        let mut text = String::new();
+        let used = match Encoding::Utf8.decode_to(&self.pending, false, self.lossy, &mut text) {
+            Ok(used) => used,
+            Err(_) => {
+                self.pending.clear();
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "The text is not valid UTF-8!",
+                ));
+            }
+        };
+        self.pending.drain(..used);
+        self.inner
+            .write_all(&self.encoding.encode(&text, self.lossy)?)?;
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Fails when the text ends in the middle of a UTF-8 character, unless `lossy`
+impl<W: FinishWrite> FinishWrite for EncodeWriter<W> {
+    fn finish(&mut self) -> Result<()> {
+        if !std::mem::take(&mut self.pending).is_empty() {
+            if !self.lossy {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    "The text is not valid UTF-8!",
+                ));
+            }
+            self.inner
+                .write_all(&self.encoding.encode("\u{FFFD}", true)?)?;
+        }
+        self.inner.finish()
+    }
+}
+
+fn valid_utf8_prefix(buf: &[u8]) -> bool {
+    match std::str::from_utf8(buf) {
+        Ok(_) => true,
+        Err(e) => e.error_len().is_none() && buf.len() - e.valid_up_to() < 4,
+    }
+}
+
+fn invalid_err(encoding: Encoding, pos: usize) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!("The text is not valid {:?} at byte {}!", encoding, pos),
+    )
+}
+
+fn unmappable_err(encoding: Encoding, ch: char) -> Error {
+    Error::new(
+        ErrorKind::InvalidData,
+        format!(
+            "The character {:?} can not be written as {:?}!",
+            ch, encoding
+        ),
+    )
+}
@@ -1,9 +1,15 @@
 pub mod console;
 pub mod convert;
+pub mod encoding;
 mod number;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+mod structured;
 
 pub use self::console::Console;
 pub use self::convert::*;
+pub use self::encoding::Encoding;
+#[cfg(any(feature = "json", feature = "toml", feature = "yaml"))]
+pub use self::structured::*;
 
 
 
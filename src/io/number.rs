@@ -1,6 +1,7 @@
 use crate::prelude::Find;
 
 #[derive(PartialEq, Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum NumberSystem {
     Binary, Octal, Decimal, Hexadecimal
 }
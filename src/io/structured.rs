@@ -0,0 +1,106 @@
+use super::convert::{ConResult, ConvertBuffer, ConvertError};
+use serde::de::DeserializeOwned;
+use std::ops::{Deref, DerefMut};
+
+macro_rules! derive_wrapper {
+    ($(#[$doc:meta] $name:ident => $feature:literal), +) => {
+        $(#[$doc]
+        #[cfg(feature = $feature)]
+        #[derive(Debug, Clone, PartialEq, Default)]
+        pub struct $name<T>(pub T);
+
+        #[cfg(feature = $feature)]
+        impl<T> $name<T> {
+            pub fn into_inner(self) -> T {
+                self.0
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T> Deref for $name<T> {
+            type Target = T;
+
+            fn deref(&self) -> &T {
+                &self.0
+            }
+        }
+
+        #[cfg(feature = $feature)]
+        impl<T> DerefMut for $name<T> {
+            fn deref_mut(&mut self) -> &mut T {
+                &mut self.0
+            }
+        })*
+    };
+}
+derive_wrapper!(
+    /// A JSON document read with `read_to_any::<Json<T>>()`
+    Json => "json",
+    /// A TOML document read with `read_to_any::<Toml<T>>()`
+    Toml => "toml",
+    /// A YAML document read with `read_to_any::<Yaml<T>>()`
+    Yaml => "yaml"
+);
+
+#[cfg(feature = "json")]
+impl<T: DeserializeOwned> ConvertBuffer for Json<T> {
+    fn from_buf(buf: Vec<u8>) -> ConResult<Self> {
+        match serde_json::from_slice(&buf) {
+            Ok(value) => Ok(Json(value)),
+            Err(e) => Err(parse_err(e.line(), e.column(), e.to_string())),
+        }
+    }
+}
+
+#[cfg(feature = "toml")]
+impl<T: DeserializeOwned> ConvertBuffer for Toml<T> {
+    fn from_buf(buf: Vec<u8>) -> ConResult<Self> {
+        let text = String::from_buf(buf)?;
+        match toml::from_str(&text) {
+            Ok(value) => Ok(Toml(value)),
+            Err(e) => {
+                let pos = e.span().map(|span| span.start).unwrap_or(0);
+                let (line, column) = line_column(&text, pos);
+                Err(parse_err(line, column, e.message().to_string()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "yaml")]
+impl<T: DeserializeOwned> ConvertBuffer for Yaml<T> {
+    fn from_buf(buf: Vec<u8>) -> ConResult<Self> {
+        match serde_yaml::from_slice(&buf) {
+            Ok(value) => Ok(Yaml(value)),
+            Err(e) => {
+                let (line, column) = match e.location() {
+                    Some(location) => (location.line(), location.column()),
+                    None => (1, 1),
+                };
+                Err(parse_err(line, column, e.to_string()))
+            }
+        }
+    }
+}
+
+/// The 1-based line and column of the byte `pos` in `text`
+#[cfg(feature = "toml")]
+fn line_column(text: &str, pos: usize) -> (usize, usize) {
+    let before = text.get(..pos).unwrap_or(text);
+    let line = before.matches('\n').count() + 1;
+    let start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    (line, before[start..].chars().count() + 1)
+}
+
+/// A `ConvertError::Parse` without the ` at line X column Y` the parsers append,
+/// the positions are at least 1 even when a parser reports 0
+fn parse_err(line: usize, column: usize, mut message: String) -> ConvertError {
+    if let Some(pos) = message.rfind(" at line ") {
+        message.truncate(pos);
+    }
+    ConvertError::Parse {
+        line: line.max(1),
+        column: column.max(1),
+        message,
+    }
+}
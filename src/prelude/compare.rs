@@ -1,4 +1,5 @@
 use super::symbols::Symbols;
+use std::cmp::Ordering;
 
 pub trait CharCompare {
     fn eq_with(&self, other: &Self) -> bool;
@@ -16,4 +17,40 @@ impl CharCompare for char {
             self == other
         }
     }
-}
\ No newline at end of file
+}
+
+/// Compare ignoring case, with runs of digits compared by their value,
+/// so `file2` comes before `file10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.chars().peekable(), b.chars().peekable());
+    loop {
+        let order = match (x.peek(), y.peek()) {
+            (None, None) => return a.cmp(b),
+            (None, _) => return Ordering::Less,
+            (_, None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let n = digits(&mut x);
+                let m = digits(&mut y);
+                let (n, m) = (n.trim_start_matches('0'), m.trim_start_matches('0'));
+                n.len().cmp(&m.len()).then_with(|| n.cmp(m))
+            }
+            (Some(c), Some(d)) => {
+                let order = c.to_lowercase().cmp(d.to_lowercase());
+                x.next();
+                y.next();
+                order
+            }
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+}
+
+fn digits(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+    let mut buf = String::new();
+    while let Some(c) = chars.next_if(|c| c.is_ascii_digit()) {
+        buf.push(c);
+    }
+    buf
+}